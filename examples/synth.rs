@@ -0,0 +1,82 @@
+use transcriber::{
+    Transcriber,
+    algorithms::{bpm_detection::bpm, pitch::PitchDetector, yin::Yin},
+    config::OnsetConfig,
+    evaluation::{NoteMetrics, onset_scores},
+    notes::{Note, NoteEvent},
+    synth::{Envelope, Reverb, Synth, Vibrato, Waveform},
+};
+
+/// Renders a melody with known notes and compares the analyses with the ground truth
+pub fn main() {
+    // Eight notes at 120 BPM, C major arpeggio
+    let melody: Vec<NoteEvent> = ["C4", "E4", "G4", "C5", "G4", "E4", "C4", "G3"]
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let note: Note = name.parse().unwrap();
+            let onset = 0.5 + i as f32 * 0.5;
+            NoteEvent::new(onset, onset + 0.45, note)
+        })
+        .collect();
+
+    let synth = Synth {
+        envelope: Envelope {
+            attack: 0.005,
+            decay: 0.15,
+            sustain: 0.5,
+            release: 0.05,
+        },
+        vibrato: Some(Vibrato {
+            rate: 5.5,
+            depth: 15.0,
+        }),
+        noise: 0.005,
+        reverb: Some(Reverb {
+            decay: 0.6,
+            mix: 0.2,
+        }),
+        ..Synth::new(Waveform::sawtooth(8))
+    };
+    let samples = synth.render(&melody);
+    println!(
+        "Rendered {} notes, {:.2} s",
+        melody.len(),
+        samples.spec.duration_milis / 1000.0
+    );
+
+    let onsets = OnsetConfig::default();
+    let odf = onsets.detection_function(&samples);
+    println!(
+        "BPM: {:.1} (expected 120)",
+        bpm(&odf, onsets.odf_rate(samples.spec.sample_rate))
+    );
+
+    let track = Yin::new(2048, 512).detect(&samples);
+    let (mut voiced, mut correct) = (0, 0);
+    for frame in track.frames.iter() {
+        let expected = melody
+            .iter()
+            .find(|n| n.onset <= frame.time && frame.time < n.offset);
+        if let (Some(f0), Some(expected)) = (frame.f0, expected) {
+            voiced += 1;
+            correct += (Note::from(f0) == expected.note) as usize;
+        }
+    }
+    println!(
+        "Yin: {} of {} voiced frames on the right note",
+        correct, voiced
+    );
+
+    let transcription = Transcriber::default().transcribe(&samples);
+    let reference: Vec<f32> = melody.iter().map(|n| n.onset).collect();
+    println!(
+        "Onsets: {}",
+        onset_scores(&reference, &transcription.onsets, 0.05)
+    );
+    let notes = NoteMetrics::default().evaluate(&melody, &transcription.notes);
+    println!(
+        "Notes: {} overlap = {:.3}",
+        notes.scores, notes.average_overlap_ratio
+    );
+}
@@ -1,5 +1,15 @@
 pub mod bpm_detection;
+pub mod chord_recognition;
+pub mod constant_q;
+pub mod contour;
+pub mod dynamics;
+pub mod key_detection;
+pub mod multi_pitch;
+pub mod note_splitting;
+pub mod note_tracking;
 pub mod onset_detection;
 pub mod peak_picking;
+pub mod pitch;
 pub mod shared;
+pub mod spectral_pitch;
 pub mod yin;
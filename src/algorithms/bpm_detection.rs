@@ -1,8 +1,13 @@
 use rustfft::FftPlanner;
 use rustfft::num_complex::Complex;
 
-pub fn compute_autocorrelation(odf: &Vec<f32>) -> Vec<f32> {
+use super::shared::OdfTiming;
+
+pub fn compute_autocorrelation(odf: &[f32]) -> Vec<f32> {
     let n = odf.len();
+    if n == 0 {
+        return Vec::new();
+    }
     let padded_len = 2 * n; // zero-pad to avoid wrap-around issues
     let mut planner = FftPlanner::new();
     let fft = planner.plan_fft_forward(padded_len);
@@ -34,13 +39,13 @@ pub fn compute_autocorrelation(odf: &Vec<f32>) -> Vec<f32> {
         .collect()
 }
 
-pub fn find_dominant_period(autocorr: &Vec<f32>, sample_rate: f32) -> f32 {
+pub fn find_dominant_period(autocorr: &[f32], sample_rate: f32) -> f32 {
     let mut max_lag = 0;
     let mut max_value = 0.0;
 
-    for lag in 10..autocorr.len() {
-        if autocorr[lag] > max_value {
-            max_value = autocorr[lag];
+    for (lag, &value) in autocorr.iter().enumerate().skip(10) {
+        if value > max_value {
+            max_value = value;
             max_lag = lag;
         }
     }
@@ -48,10 +53,72 @@ pub fn find_dominant_period(autocorr: &Vec<f32>, sample_rate: f32) -> f32 {
     max_lag as f32 / sample_rate
 }
 
-pub fn bpm(odf: &Vec<f32>, sample_rate: f32) -> f32 {
+/// Tempo of the dominant period of `odf`, or 0 if its autocorrelation has no positive
+/// value past the shortest lag
+pub fn bpm(odf: &[f32], sample_rate: f32) -> f32 {
     let autocorr = compute_autocorrelation(odf);
     let dominant_period = find_dominant_period(&autocorr, sample_rate);
+    if dominant_period <= 0.0 {
+        return 0.0;
+    }
 
     // Convert period to BPM
     60.0 / dominant_period
 }
+
+/// A periodicity of the onset detection function
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoCandidate {
+    pub bpm: f32,
+    /// Autocorrelation at the period, relative to the one at lag 0
+    pub strength: f32,
+}
+
+/// Local maxima of the ODF autocorrelation as tempi, strongest first. Only lags from 10
+/// frames on are considered, as in `find_dominant_period`.
+pub fn tempo_candidates(odf: &[f32], sample_rate: f32, count: usize) -> Vec<TempoCandidate> {
+    let autocorr = compute_autocorrelation(odf);
+    let energy = autocorr.first().copied().unwrap_or(0.0).max(f32::EPSILON);
+
+    let mut candidates: Vec<TempoCandidate> = autocorr
+        .iter()
+        .enumerate()
+        .skip(10)
+        .take(autocorr.len().saturating_sub(11))
+        .filter(|&(lag, &value)| value > autocorr[lag - 1] && value >= autocorr[lag + 1])
+        .map(|(lag, &value)| TempoCandidate {
+            bpm: 60.0 * sample_rate / lag as f32,
+            strength: value / energy,
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.strength.total_cmp(&a.strength));
+    candidates.truncate(count);
+    candidates
+}
+
+/// Beat times in seconds of a constant tempo, with the phase that collects the most
+/// ODF energy. `odf` has the time base `timing`.
+pub fn beat_grid(odf: &[f32], timing: OdfTiming, bpm: f32) -> Vec<f32> {
+    let period = 60.0 * timing.rate() / bpm;
+    if odf.is_empty() || !period.is_finite() || period < 1.0 {
+        return Vec::new();
+    }
+
+    let beats = |phase: f32| {
+        (0..)
+            .map(move |k| phase + k as f32 * period)
+            .take_while(|&frame| (frame.round() as usize) < odf.len())
+    };
+    let phase = (0..period.ceil() as usize)
+        .map(|phase| phase as f32)
+        .max_by(|&a, &b| {
+            let score = |phase| beats(phase).map(|f| odf[f.round() as usize]).sum::<f32>();
+            score(a).total_cmp(&score(b))
+        })
+        .unwrap_or(0.0);
+
+    // Beats fall between frames, so interpolate from the first one
+    beats(phase)
+        .map(|frame| timing.time(0) + frame / timing.rate())
+        .collect()
+}
@@ -0,0 +1,178 @@
+use super::constant_q::Chromagram;
+use crate::{
+    chords::{Chord, ChordQuality},
+    notes::NoteName,
+};
+
+/// A chord, or no chord at all, held between two instants
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChordSpan {
+    /// Start of the span in seconds
+    pub start: f32,
+    /// End of the span in seconds
+    pub end: f32,
+    /// `None` for silence or material that matches no chord
+    pub chord: Option<Chord>,
+}
+
+impl ChordSpan {
+    pub fn duration(&self) -> f32 {
+        self.end - self.start
+    }
+}
+
+/// Template matching over the chromagram, smoothed with a Viterbi pass that charges a
+/// penalty for every chord change.
+#[derive(Debug, Clone)]
+pub struct ChordRecognizer {
+    /// Chord qualities to look for, on every root
+    pub qualities: Vec<ChordQuality>,
+    /// Cost of changing chord between two frames, in units of template similarity
+    pub switch_penalty: f32,
+    /// Frames whose best cosine similarity is lower are labeled as no chord
+    pub min_similarity: f32,
+    /// Spans shorter than this, in seconds, are merged into the previous one
+    pub min_duration: f32,
+}
+
+impl Default for ChordRecognizer {
+    fn default() -> Self {
+        ChordRecognizer {
+            qualities: ChordQuality::ALL.to_vec(),
+            switch_penalty: 1.0,
+            min_similarity: 0.6,
+            min_duration: 0.2,
+        }
+    }
+}
+
+impl ChordRecognizer {
+    /// Best chord of every frame, before smoothing
+    pub fn frames(&self, chroma: &Chromagram) -> Vec<Option<Chord>> {
+        let candidates = self.candidates();
+        chroma
+            .frames
+            .iter()
+            .map(|frame| {
+                let similarities = similarities(frame, &candidates, self.min_similarity);
+                argmax(&similarities).and_then(|i| candidates[i])
+            })
+            .collect()
+    }
+
+    pub fn recognize(&self, chroma: &Chromagram) -> Vec<ChordSpan> {
+        let candidates = self.candidates();
+        let similarities: Vec<Vec<f32>> = chroma
+            .frames
+            .iter()
+            .map(|frame| similarities(frame, &candidates, self.min_similarity))
+            .collect();
+
+        let labels = self.viterbi(&similarities);
+        let frame_duration = 1.0 / chroma.frame_rate();
+
+        let mut spans: Vec<ChordSpan> = Vec::new();
+        for (i, label) in labels.iter().enumerate() {
+            let chord = candidates[*label];
+            let time = i as f32 * frame_duration;
+            match spans.last_mut() {
+                Some(span) if span.chord == chord => span.end = time + frame_duration,
+                _ => spans.push(ChordSpan {
+                    start: time,
+                    end: time + frame_duration,
+                    chord,
+                }),
+            }
+        }
+
+        self.merge_short(spans)
+    }
+
+    /// Every chord to look for, plus `None` for no chord
+    fn candidates(&self) -> Vec<Option<Chord>> {
+        let mut candidates = vec![None];
+        for quality in self.qualities.iter() {
+            for root in 0..12 {
+                candidates.push(Some(Chord::new(NoteName::from_pitch_class(root), *quality)));
+            }
+        }
+        candidates
+    }
+
+    /// Label sequence maximizing the total similarity minus the switching penalties
+    fn viterbi(&self, similarities: &[Vec<f32>]) -> Vec<usize> {
+        let Some(first) = similarities.first() else {
+            return Vec::new();
+        };
+        let states = first.len();
+        let mut score = first.clone();
+        let mut backpointers: Vec<Vec<usize>> = Vec::with_capacity(similarities.len());
+
+        for frame in similarities.iter().skip(1) {
+            let best = argmax(&score).unwrap();
+            let switched = score[best] - self.switch_penalty;
+            let mut pointers = vec![0; states];
+            let mut next = vec![0.0; states];
+            for s in 0..states {
+                let (from, previous) = if score[s] >= switched {
+                    (s, score[s])
+                } else {
+                    (best, switched)
+                };
+                pointers[s] = from;
+                next[s] = previous + frame[s];
+            }
+            backpointers.push(pointers);
+            score = next;
+        }
+
+        let mut state = argmax(&score).unwrap();
+        let mut labels = vec![state];
+        for pointers in backpointers.iter().rev() {
+            state = pointers[state];
+            labels.push(state);
+        }
+        labels.reverse();
+        labels
+    }
+
+    fn merge_short(&self, spans: Vec<ChordSpan>) -> Vec<ChordSpan> {
+        let mut merged: Vec<ChordSpan> = Vec::with_capacity(spans.len());
+        for span in spans {
+            match merged.last_mut() {
+                Some(last) if span.duration() < self.min_duration || last.chord == span.chord => {
+                    last.end = span.end
+                }
+                _ => merged.push(span),
+            }
+        }
+        merged
+    }
+}
+
+/// Cosine similarity of `frame` with the template of every candidate. No chord scores
+/// the minimum similarity, so it only wins when nothing else matches well.
+fn similarities(frame: &[f32; 12], candidates: &[Option<Chord>], min_similarity: f32) -> Vec<f32> {
+    let norm = frame.iter().map(|c| c * c).sum::<f32>().sqrt();
+    candidates
+        .iter()
+        .map(|candidate| match (candidate, norm > f32::EPSILON) {
+            (Some(chord), true) => {
+                let template = chord.template();
+                let template_norm = (chord.quality.intervals().len() as f32).sqrt();
+                let dot: f32 = frame.iter().zip(template.iter()).map(|(c, t)| c * t).sum();
+                dot / (norm * template_norm)
+            }
+            (Some(_), false) => 0.0,
+            (None, _) => min_similarity,
+        })
+        .collect()
+}
+
+fn argmax(values: &[f32]) -> Option<usize> {
+    values
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(i, _)| i)
+}
@@ -0,0 +1,209 @@
+use rustfft::{FftPlanner, num_complex::Complex};
+
+use super::shared::hamming_window;
+use crate::{samples::Samples, tuning::Tuning};
+
+/// Spectral kernel values below this fraction of the peak are dropped
+const SPARSITY: f32 = 0.0054;
+
+/// Non-zero (FFT bin, weight) pairs of a spectral kernel
+type SparseKernel = Vec<(usize, Complex<f32>)>;
+
+/// Constant-Q transform computed with sparse spectral kernels over the FFT of every
+/// frame (Brown & Puckette, 1992).
+#[derive(Debug, Clone)]
+pub struct ConstantQ {
+    /// Center frequency of the first bin in Hz
+    pub fmin: f32,
+    pub bins_per_octave: usize,
+    pub octaves: usize,
+    pub hop_size: usize,
+}
+
+impl Default for ConstantQ {
+    fn default() -> Self {
+        ConstantQ {
+            // C1
+            fmin: 32.703,
+            bins_per_octave: 36,
+            octaves: 7,
+            hop_size: 2048,
+        }
+    }
+}
+
+/// Magnitude of every constant-Q bin, frame by frame
+#[derive(Debug, Clone)]
+pub struct Cqt {
+    pub sample_rate: u32,
+    pub hop_size: usize,
+    pub fmin: f32,
+    pub bins_per_octave: usize,
+    /// Frame `i` is centered at `i * hop_size` samples
+    pub frames: Vec<Vec<f32>>,
+}
+
+/// Energy of each of the 12 pitch classes, indexed from C, frame by frame
+#[derive(Debug, Clone)]
+pub struct Chromagram {
+    pub sample_rate: u32,
+    pub hop_size: usize,
+    /// Frame `i` is centered at `i * hop_size` samples. Every frame is normalized to a
+    /// maximum of 1, except silent ones.
+    pub frames: Vec<[f32; 12]>,
+}
+
+impl ConstantQ {
+    pub fn new(fmin: f32, bins_per_octave: usize, octaves: usize, hop_size: usize) -> Self {
+        ConstantQ {
+            fmin,
+            bins_per_octave,
+            octaves,
+            hop_size,
+        }
+    }
+
+    /// Quality factor: ratio of center frequency to bandwidth
+    pub fn q(&self) -> f32 {
+        1.0 / (2.0f32.powf(1.0 / self.bins_per_octave as f32) - 1.0)
+    }
+
+    pub fn bins(&self) -> usize {
+        self.bins_per_octave * self.octaves
+    }
+
+    pub fn frequency(&self, bin: usize) -> f32 {
+        self.fmin * 2.0f32.powf(bin as f32 / self.bins_per_octave as f32)
+    }
+
+    pub fn transform(&self, samples: &Samples) -> Cqt {
+        let sample_rate = samples.spec.sample_rate;
+        let (fft_size, kernels) = self.kernels(sample_rate as f32);
+
+        let mut planner = FftPlanner::new();
+        let fft = planner.plan_fft_forward(fft_size);
+
+        // Zero-pad so that frame i is centered at i * hop_size
+        let mut padded = vec![0.0; fft_size / 2];
+        padded.extend_from_slice(samples);
+        padded.resize(padded.len() + fft_size / 2, 0.0);
+
+        let frames = padded
+            .windows(fft_size)
+            .step_by(self.hop_size)
+            .map(|frame| {
+                let mut spectrum: Vec<Complex<f32>> =
+                    frame.iter().map(|s| Complex::new(*s, 0.0)).collect();
+                fft.process(&mut spectrum);
+                kernels
+                    .iter()
+                    .map(|kernel| {
+                        kernel
+                            .iter()
+                            .map(|(j, k)| spectrum[*j] * k)
+                            .sum::<Complex<f32>>()
+                            .norm()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Cqt {
+            sample_rate,
+            hop_size: self.hop_size,
+            fmin: self.fmin,
+            bins_per_octave: self.bins_per_octave,
+            frames,
+        }
+    }
+
+    /// FFT size and the sparse spectral kernel of every bin
+    fn kernels(&self, sample_rate: f32) -> (usize, Vec<SparseKernel>) {
+        let q = self.q();
+        let longest = (q * sample_rate / self.fmin).ceil() as usize;
+        let fft_size = longest.next_power_of_two();
+
+        let mut planner = FftPlanner::new();
+        let fft = planner.plan_fft_forward(fft_size);
+
+        let kernels = (0..self.bins())
+            .map(|bin| {
+                let length = ((q * sample_rate / self.frequency(bin)).ceil() as usize).max(2);
+                let window = hamming_window(length);
+                let start = (fft_size - length) / 2;
+
+                let mut kernel = vec![Complex::new(0.0, 0.0); fft_size];
+                for (n, w) in window.iter().enumerate() {
+                    let phase = 2.0 * std::f32::consts::PI * q * n as f32 / length as f32;
+                    kernel[start + n] = Complex::from_polar(w / length as f32, phase);
+                }
+                fft.process(&mut kernel);
+
+                let peak = kernel.iter().map(|k| k.norm()).fold(0.0, f32::max);
+                kernel
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, k)| k.norm() >= SPARSITY * peak)
+                    .map(|(j, k)| (j, k.conj() / fft_size as f32))
+                    .collect()
+            })
+            .collect();
+
+        (fft_size, kernels)
+    }
+}
+
+impl Cqt {
+    pub fn frequency(&self, bin: usize) -> f32 {
+        self.fmin * 2.0f32.powf(bin as f32 / self.bins_per_octave as f32)
+    }
+
+    /// Folds every bin into the pitch class of its nearest note in `tuning`
+    pub fn chromagram(&self, tuning: &Tuning) -> Chromagram {
+        let bins = self.frames.first().map_or(0, |f| f.len());
+        let pitch_classes: Vec<usize> = (0..bins)
+            .map(|bin| {
+                (tuning.semitones(self.frequency(bin)).round() as i32).rem_euclid(12) as usize
+            })
+            .collect();
+
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| {
+                let mut chroma = [0.0; 12];
+                for (bin, magnitude) in frame.iter().enumerate() {
+                    chroma[pitch_classes[bin]] += magnitude * magnitude;
+                }
+                let max = chroma.iter().copied().fold(0.0, f32::max);
+                if max > f32::EPSILON {
+                    chroma.iter_mut().for_each(|c| *c /= max);
+                }
+                chroma
+            })
+            .collect();
+
+        Chromagram {
+            sample_rate: self.sample_rate,
+            hop_size: self.hop_size,
+            frames,
+        }
+    }
+}
+
+impl Chromagram {
+    pub fn frame_rate(&self) -> f32 {
+        self.sample_rate as f32 / self.hop_size as f32
+    }
+
+    /// Average chroma vector, e.g. as input to [`super::key_detection::estimate_key`]
+    pub fn mean(&self) -> [f32; 12] {
+        let mut mean = [0.0; 12];
+        for frame in self.frames.iter() {
+            for (m, c) in mean.iter_mut().zip(frame.iter()) {
+                *m += c / self.frames.len() as f32;
+            }
+        }
+        mean
+    }
+}
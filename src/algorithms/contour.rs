@@ -0,0 +1,94 @@
+use super::pitch::PitchTrack;
+use crate::notes::NoteEvent;
+
+/// Length in seconds of the start and end regions used to measure glides
+const GLIDE_WINDOW: f32 = 0.05;
+
+/// Center value of a 14-bit MIDI pitch bend
+pub const PITCH_BEND_CENTER: u16 = 8192;
+
+/// Intonation of a single note, measured against its nominal frequency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PitchContour {
+    /// Time and deviation in cents of every voiced frame within the note
+    pub cents: Vec<(f32, f32)>,
+    /// Mean deviation in cents
+    pub mean: f32,
+    /// Slope of the least-squares line through the contour, in cents per second
+    pub drift: f32,
+    /// Mean deviation over the 50 ms from the first voiced frame of the note, which
+    /// may come after its onset
+    pub glide_start: f32,
+    /// Mean deviation over the 50 ms up to the last voiced frame of the note, which
+    /// may come before its offset
+    pub glide_end: f32,
+}
+
+impl PitchContour {
+    /// Contour of `event` over the frames of `track`. `None` if no frame within the
+    /// note is voiced with at least `min_voiced_prob`.
+    pub fn new(event: &NoteEvent, track: &PitchTrack, min_voiced_prob: f32) -> Option<Self> {
+        let cents: Vec<(f32, f32)> = track
+            .frames
+            .iter()
+            .filter(|f| f.time >= event.onset && f.time < event.offset)
+            .filter(|f| f.voiced_prob >= min_voiced_prob)
+            .filter_map(|f| f.f0.map(|f0| (f.time, event.note.cents(f0))))
+            .collect();
+
+        if cents.is_empty() {
+            return None;
+        }
+
+        let n = cents.len() as f32;
+        let mean_time = cents.iter().map(|c| c.0).sum::<f32>() / n;
+        let mean = cents.iter().map(|c| c.1).sum::<f32>() / n;
+
+        let covariance: f32 = cents
+            .iter()
+            .map(|(t, c)| (t - mean_time) * (c - mean))
+            .sum();
+        let variance: f32 = cents.iter().map(|(t, _)| (t - mean_time).powi(2)).sum();
+        let drift = if variance > 0.0 {
+            covariance / variance
+        } else {
+            0.0
+        };
+
+        let window_mean = |from: f32, to: f32| {
+            let window: Vec<f32> = cents
+                .iter()
+                .filter(|(t, _)| *t >= from && *t <= to)
+                .map(|c| c.1)
+                .collect();
+            window.iter().sum::<f32>() / window.len().max(1) as f32
+        };
+        let (first, last) = (cents[0].0, cents[cents.len() - 1].0);
+        let glide_start = window_mean(first, first + GLIDE_WINDOW);
+        let glide_end = window_mean(last - GLIDE_WINDOW, last);
+
+        Some(PitchContour {
+            cents,
+            mean,
+            drift,
+            glide_start,
+            glide_end,
+        })
+    }
+
+    /// MIDI pitch bend value of every frame for a synth with a bend range of
+    /// ±`range` semitones
+    pub fn pitch_bends(&self, range: f32) -> Vec<(f32, u16)> {
+        self.cents
+            .iter()
+            .map(|(t, c)| (*t, cents_to_pitch_bend(*c, range)))
+            .collect()
+    }
+}
+
+/// 14-bit MIDI pitch bend value for a deviation of `cents`, given a bend range of
+/// ±`range` semitones
+pub fn cents_to_pitch_bend(cents: f32, range: f32) -> u16 {
+    let bend = PITCH_BEND_CENTER as f32 + cents / (range * 100.0) * PITCH_BEND_CENTER as f32;
+    bend.round().clamp(0.0, 16383.0) as u16
+}
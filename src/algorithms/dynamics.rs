@@ -0,0 +1,194 @@
+use std::fmt;
+
+use rustfft::{FftPlanner, num_complex::Complex};
+
+use crate::{notes::NoteEvent, samples::Samples};
+
+/// Levels below this, in dBFS, are reported as this value
+const SILENCE_DB: f32 = -120.0;
+
+/// Frequency weighting applied before measuring loudness
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weighting {
+    Flat,
+    /// IEC 61672 A-weighting, normalized to 0 dB at 1 kHz
+    A,
+}
+
+impl Weighting {
+    /// Amplitude gain of the weighting at `freq` Hz
+    pub fn gain(&self, freq: f32) -> f32 {
+        match self {
+            Weighting::Flat => 1.0,
+            Weighting::A => {
+                let f2 = freq * freq;
+                let ra = 12194.0f32.powi(2) * f2 * f2
+                    / ((f2 + 20.6f32.powi(2))
+                        * ((f2 + 107.7f32.powi(2)) * (f2 + 737.9f32.powi(2))).sqrt()
+                        * (f2 + 12194.0f32.powi(2)));
+                // +2.00 dB, so that the gain at 1 kHz is 1
+                ra * 1.258_925_4
+            }
+        }
+    }
+}
+
+/// Dynamic marking, from pianissimo to fortissimo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Dynamic {
+    Pianissimo,
+    Piano,
+    MezzoPiano,
+    MezzoForte,
+    Forte,
+    Fortissimo,
+}
+
+impl Dynamic {
+    pub fn from_velocity(velocity: u8) -> Self {
+        match velocity {
+            0..40 => Dynamic::Pianissimo,
+            40..56 => Dynamic::Piano,
+            56..72 => Dynamic::MezzoPiano,
+            72..88 => Dynamic::MezzoForte,
+            88..104 => Dynamic::Forte,
+            _ => Dynamic::Fortissimo,
+        }
+    }
+
+    /// Nominal MIDI velocity of the marking
+    pub fn velocity(&self) -> u8 {
+        match self {
+            Dynamic::Pianissimo => 33,
+            Dynamic::Piano => 49,
+            Dynamic::MezzoPiano => 64,
+            Dynamic::MezzoForte => 80,
+            Dynamic::Forte => 96,
+            Dynamic::Fortissimo => 112,
+        }
+    }
+}
+
+impl fmt::Display for Dynamic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marking = match self {
+            Dynamic::Pianissimo => "pp",
+            Dynamic::Piano => "p",
+            Dynamic::MezzoPiano => "mp",
+            Dynamic::MezzoForte => "mf",
+            Dynamic::Forte => "f",
+            Dynamic::Fortissimo => "ff",
+        };
+        write!(f, "{}", marking)
+    }
+}
+
+/// Level measurements of a single note
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteDynamics {
+    /// RMS level in dBFS
+    pub rms_db: f32,
+    /// Peak level in dBFS
+    pub peak_db: f32,
+    /// Weighted RMS level in dBFS, where a full-scale 1 kHz sine measures -3 dB as in
+    /// LUFS
+    pub loudness_db: f32,
+    pub velocity: u8,
+    pub dynamic: Dynamic,
+}
+
+/// Measures the level of every note of a recording and maps its loudness linearly (in
+/// dB) to a MIDI velocity.
+#[derive(Debug, Clone)]
+pub struct DynamicsEstimator {
+    pub weighting: Weighting,
+    /// Loudness in dBFS mapped to velocity 1
+    pub floor_db: f32,
+    /// Loudness in dBFS mapped to velocity 127
+    pub ceiling_db: f32,
+}
+
+impl Default for DynamicsEstimator {
+    fn default() -> Self {
+        DynamicsEstimator {
+            weighting: Weighting::A,
+            floor_db: -60.0,
+            ceiling_db: -6.0,
+        }
+    }
+}
+
+impl DynamicsEstimator {
+    pub fn new(weighting: Weighting) -> Self {
+        DynamicsEstimator {
+            weighting,
+            ..Default::default()
+        }
+    }
+
+    /// Dynamics of every event, measured over the samples between its onset and offset
+    pub fn estimate(&self, samples: &Samples, events: &[NoteEvent]) -> Vec<NoteDynamics> {
+        let sr = samples.spec.sample_rate as f32;
+        let mut planner = FftPlanner::new();
+
+        events
+            .iter()
+            .map(|event| {
+                let start = ((event.onset * sr) as usize).min(samples.len());
+                let end = ((event.offset * sr) as usize).clamp(start, samples.len());
+                let segment = &samples[start..end];
+                if segment.is_empty() {
+                    return self.note_dynamics(SILENCE_DB, SILENCE_DB, SILENCE_DB);
+                }
+
+                let mean_square = segment.iter().map(|s| s * s).sum::<f32>() / segment.len() as f32;
+                let peak = segment.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+
+                // Parseval over the zero-padded spectrum, with every bin weighted
+                let fft_size = segment.len().next_power_of_two();
+                let mut spectrum: Vec<Complex<f32>> =
+                    segment.iter().map(|s| Complex::new(*s, 0.0)).collect();
+                spectrum.resize(fft_size, Complex::new(0.0, 0.0));
+                planner.plan_fft_forward(fft_size).process(&mut spectrum);
+                let weighted = spectrum
+                    .iter()
+                    .enumerate()
+                    .map(|(k, x)| {
+                        let bin = k.min(fft_size - k);
+                        let gain = self.weighting.gain(bin as f32 * sr / fft_size as f32);
+                        x.norm_sqr() * gain * gain
+                    })
+                    .sum::<f32>()
+                    / (fft_size * segment.len()) as f32;
+
+                self.note_dynamics(
+                    power_to_db(mean_square),
+                    power_to_db(peak * peak),
+                    power_to_db(weighted),
+                )
+            })
+            .collect()
+    }
+
+    /// Velocity in 1..=127 of a loudness in dBFS
+    pub fn velocity(&self, loudness_db: f32) -> u8 {
+        let range = (self.ceiling_db - self.floor_db).max(f32::EPSILON);
+        let position = ((loudness_db - self.floor_db) / range).clamp(0.0, 1.0);
+        1 + (position * 126.0).round() as u8
+    }
+
+    fn note_dynamics(&self, rms_db: f32, peak_db: f32, loudness_db: f32) -> NoteDynamics {
+        let velocity = self.velocity(loudness_db);
+        NoteDynamics {
+            rms_db,
+            peak_db,
+            loudness_db,
+            velocity,
+            dynamic: Dynamic::from_velocity(velocity),
+        }
+    }
+}
+
+fn power_to_db(power: f32) -> f32 {
+    (10.0 * power.log10()).max(SILENCE_DB)
+}
@@ -0,0 +1,105 @@
+use crate::{
+    key::{Key, Mode},
+    notes::{NoteEvent, NoteName},
+};
+
+/// Pitch-class weights of each key, starting at the tonic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyProfile {
+    /// Probe-tone ratings from Krumhansl & Kessler (1982)
+    KrumhanslKessler,
+    /// Corpus-based weights from Temperley (1999)
+    Temperley,
+}
+
+impl KeyProfile {
+    fn weights(&self, mode: Mode) -> [f32; 12] {
+        match (self, mode) {
+            (KeyProfile::KrumhanslKessler, Mode::Major) => [
+                6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+            ],
+            (KeyProfile::KrumhanslKessler, Mode::Minor) => [
+                6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+            ],
+            (KeyProfile::Temperley, Mode::Major) => {
+                [5.0, 2.0, 3.5, 2.0, 4.5, 4.0, 2.0, 4.5, 2.0, 3.5, 1.5, 4.0]
+            }
+            (KeyProfile::Temperley, Mode::Minor) => {
+                [5.0, 2.0, 3.5, 4.5, 2.0, 4.0, 2.0, 4.5, 3.5, 2.0, 1.5, 4.0]
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyEstimate {
+    pub key: Key,
+    /// Pearson correlation between the input and the key profile
+    pub correlation: f32,
+    /// Margin in correlation over the next best key
+    pub confidence: f32,
+}
+
+/// Total duration in seconds of every pitch class in `events`, indexed from C
+pub fn pitch_class_durations(events: &[NoteEvent]) -> [f32; 12] {
+    let mut durations = [0.0; 12];
+    for event in events {
+        durations[event.note.name.pitch_class() as usize] += event.duration().max(0.0);
+    }
+    durations
+}
+
+/// All 24 major and minor keys ranked by their correlation with `distribution`, a
+/// pitch-class histogram or chroma vector indexed from C
+pub fn rank_keys(distribution: &[f32; 12], profile: KeyProfile) -> Vec<(Key, f32)> {
+    let mut ranking = Vec::with_capacity(24);
+    for mode in [Mode::Major, Mode::Minor] {
+        let weights = profile.weights(mode);
+        for tonic in 0..12 {
+            let rotated: Vec<f32> = (0..12).map(|i| weights[(i + 12 - tonic) % 12]).collect();
+            ranking.push((
+                Key::new(NoteName::from_pitch_class(tonic as u8), mode),
+                correlation(distribution, &rotated),
+            ));
+        }
+    }
+    ranking.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranking
+}
+
+/// Most likely key of `distribution`, a pitch-class histogram or chroma vector indexed
+/// from C. `None` if the distribution is flat (e.g. no notes at all).
+pub fn estimate_key(distribution: &[f32; 12], profile: KeyProfile) -> Option<KeyEstimate> {
+    let ranking = rank_keys(distribution, profile);
+    let (key, correlation) = ranking[0];
+    if correlation.is_nan() {
+        return None;
+    }
+    Some(KeyEstimate {
+        key,
+        correlation,
+        confidence: correlation - ranking[1].1,
+    })
+}
+
+/// Krumhansl-Schmuckler key finding over the durations of the transcribed notes
+pub fn detect_key(events: &[NoteEvent]) -> Option<KeyEstimate> {
+    estimate_key(&pitch_class_durations(events), KeyProfile::KrumhanslKessler)
+}
+
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len() as f32;
+    let mean_a = a.iter().sum::<f32>() / n;
+    let mean_b = b.iter().sum::<f32>() / n;
+
+    let mut covariance = 0.0;
+    let mut variance_a = 0.0;
+    let mut variance_b = 0.0;
+    for (x, y) in a.iter().zip(b.iter()) {
+        covariance += (x - mean_a) * (y - mean_b);
+        variance_a += (x - mean_a).powi(2);
+        variance_b += (y - mean_b).powi(2);
+    }
+
+    covariance / (variance_a * variance_b).sqrt()
+}
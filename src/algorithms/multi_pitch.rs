@@ -0,0 +1,251 @@
+use super::shared::padded_stft;
+use crate::{
+    notes::{Note, NoteEvent},
+    samples::Samples,
+    tuning::Tuning,
+};
+
+/// Weights of the partials in the salience function, from Klapuri (2006)
+const ALPHA: f32 = 52.0;
+const BETA: f32 = 320.0;
+/// Fraction of every detected (smoothed) partial removed from the residual spectrum
+const CANCELLATION: f32 = 0.89;
+
+/// Active pitches of a single analysis frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiPitchFrame {
+    /// Center of the frame in seconds
+    pub time: f32,
+    /// Fundamental frequencies in Hz, in order of detection
+    pub f0s: Vec<f32>,
+    /// Salience of every fundamental
+    pub saliences: Vec<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MultiPitchTrack {
+    pub sample_rate: u32,
+    pub hop_size: usize,
+    pub frames: Vec<MultiPitchFrame>,
+}
+
+impl MultiPitchTrack {
+    /// Active notes of every frame
+    pub fn notes(&self, tuning: &Tuning) -> Vec<Vec<Note>> {
+        self.frames
+            .iter()
+            .map(|f| {
+                let mut notes: Vec<Note> = f.f0s.iter().map(|f0| tuning.note(*f0)).collect();
+                notes.sort();
+                notes.dedup();
+                notes
+            })
+            .collect()
+    }
+
+    /// Joins the frames where a note stays active into events, dropping the ones
+    /// shorter than `min_duration` seconds
+    pub fn events(&self, tuning: &Tuning, min_duration: f32) -> Vec<NoteEvent> {
+        let frame_duration = self.hop_size as f32 / self.sample_rate as f32;
+        let notes = self.notes(tuning);
+        let mut active: Vec<(Note, f32)> = Vec::new();
+        let mut events = Vec::new();
+
+        for (frame, notes) in self.frames.iter().zip(notes.iter()) {
+            active.retain(|(note, onset)| {
+                let keep = notes.contains(note);
+                if !keep {
+                    events.push(NoteEvent::new(*onset, frame.time, *note));
+                }
+                keep
+            });
+            for note in notes {
+                if !active.iter().any(|(n, _)| n == note) {
+                    active.push((*note, frame.time));
+                }
+            }
+        }
+
+        let end = self.frames.last().map(|f| f.time + frame_duration);
+        for (note, onset) in active {
+            events.push(NoteEvent::new(onset, end.unwrap_or(onset), note));
+        }
+
+        events.retain(|e| e.duration() >= min_duration);
+        events.sort_by(|a, b| a.onset.total_cmp(&b.onset).then(a.note.cmp(&b.note)));
+        events
+    }
+}
+
+/// Multiple-f0 estimator based on iterative estimation and cancellation of the most
+/// salient harmonic series (Klapuri, 2006).
+#[derive(Debug, Clone)]
+pub struct MultiPitch {
+    pub frame_size: usize,
+    pub hop_size: usize,
+    /// Frames are zero-padded to `frame_size * zero_padding` before the FFT
+    pub zero_padding: usize,
+    /// Minimum frequency in Hz
+    pub fmin: f32,
+    /// Maximum frequency in Hz
+    pub fmax: f32,
+    /// Spacing of the f0 candidates in cents
+    pub resolution: f32,
+    /// Number of partials summed for every candidate
+    pub harmonics: usize,
+    /// Upper bound on simultaneous pitches
+    pub max_polyphony: usize,
+    /// Pitches with a salience below this fraction of the strongest one are discarded
+    pub min_salience: f32,
+    /// Frames with a lower RMS are considered silent
+    pub power_threshold: f32,
+}
+
+impl Default for MultiPitch {
+    fn default() -> Self {
+        MultiPitch {
+            frame_size: 4096,
+            hop_size: 1024,
+            zero_padding: 2,
+            fmin: 40.0,
+            fmax: 2100.0,
+            resolution: 10.0,
+            harmonics: 20,
+            max_polyphony: 6,
+            min_salience: 0.4,
+            power_threshold: 1e-3,
+        }
+    }
+}
+
+impl MultiPitch {
+    pub fn new(frame_size: usize, hop_size: usize) -> Self {
+        MultiPitch {
+            frame_size,
+            hop_size,
+            ..Default::default()
+        }
+    }
+
+    pub fn detect(&mut self, samples: &Samples) -> MultiPitchTrack {
+        let sample_rate = samples.spec.sample_rate;
+        let sr = sample_rate as f32;
+        let fft_size = self.frame_size * self.zero_padding.max(1);
+        let bin_hz = sr / fft_size as f32;
+
+        let steps = (1200.0 * (self.fmax / self.fmin).log2() / self.resolution) as usize;
+        let candidates: Vec<f32> = (0..=steps)
+            .map(|i| self.fmin * 2.0f32.powf(i as f32 * self.resolution / 1200.0))
+            .collect();
+
+        let frames = padded_stft(samples, self.frame_size, self.hop_size, fft_size)
+            .iter()
+            .enumerate()
+            .map(|(i, spectrum)| {
+                let start = i * self.hop_size;
+                let time = (start + self.frame_size / 2) as f32 / sr;
+                let frame = &samples[start..start + self.frame_size];
+                let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt();
+
+                let (f0s, saliences) = if rms < self.power_threshold {
+                    (Vec::new(), Vec::new())
+                } else {
+                    let residual = spectrum[..fft_size / 2].iter().map(|c| c.norm()).collect();
+                    self.estimate(residual, &candidates, bin_hz)
+                };
+
+                MultiPitchFrame {
+                    time,
+                    f0s,
+                    saliences,
+                }
+            })
+            .collect();
+
+        MultiPitchTrack {
+            sample_rate,
+            hop_size: self.hop_size,
+            frames,
+        }
+    }
+
+    /// Iteratively picks the most salient candidate and cancels its partials from the
+    /// residual magnitude spectrum
+    fn estimate(
+        &self,
+        mut residual: Vec<f32>,
+        candidates: &[f32],
+        bin_hz: f32,
+    ) -> (Vec<f32>, Vec<f32>) {
+        let mut f0s = Vec::new();
+        let mut saliences: Vec<f32> = Vec::new();
+        // Half width in bins of the main lobe of the Hamming window
+        let lobe = 2 * self.zero_padding.max(1);
+
+        while f0s.len() < self.max_polyphony {
+            let Some((f0, salience)) = candidates
+                .iter()
+                .map(|f0| (*f0, self.salience(&residual, *f0, bin_hz)))
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+            else {
+                break;
+            };
+
+            if salience <= 0.0 || salience < self.min_salience * saliences.first().unwrap_or(&0.0) {
+                break;
+            }
+
+            // Spectral smoothness: a partial higher than its neighbours is probably
+            // shared with another note, so only part of it is cancelled
+            let partials: Vec<Option<(usize, f32)>> = (1..=self.harmonics)
+                .map(|h| partial(&residual, f0 * h as f32, bin_hz))
+                .collect();
+            let magnitude = |h: usize| partials.get(h).copied().flatten().map_or(0.0, |p| p.1);
+            for (h, p) in partials.iter().enumerate() {
+                let Some((bin, peak)) = *p else { break };
+                if peak <= 0.0 {
+                    continue;
+                }
+                let neighbours = (magnitude(h.wrapping_sub(1)) + peak + magnitude(h + 1)) / 3.0;
+                let factor = 1.0 - CANCELLATION * peak.min(neighbours) / peak;
+                for k in bin.saturating_sub(lobe)..=(bin + lobe).min(residual.len() - 1) {
+                    residual[k] *= factor;
+                }
+            }
+
+            f0s.push(f0);
+            saliences.push(salience);
+        }
+
+        (f0s, saliences)
+    }
+
+    /// Weighted sum of the partials of `f0` in `spectrum`
+    fn salience(&self, spectrum: &[f32], f0: f32, bin_hz: f32) -> f32 {
+        (1..=self.harmonics)
+            .map_while(|h| {
+                let (_, magnitude) = partial(spectrum, f0 * h as f32, bin_hz)?;
+                Some((f0 + ALPHA) / (h as f32 * f0 + BETA) * magnitude)
+            })
+            .sum()
+    }
+}
+
+/// Strongest spectral peak within a quarter tone of `freq`, along with its magnitude,
+/// which is zero if there is no peak but only the skirt of a neighbouring one. `None`
+/// above the Nyquist frequency.
+fn partial(spectrum: &[f32], freq: f32, bin_hz: f32) -> Option<(usize, f32)> {
+    const QUARTER_TONE: f32 = 1.029_302_2;
+    let lo = ((freq / QUARTER_TONE / bin_hz).floor() as usize).max(1);
+    let hi = ((freq * QUARTER_TONE / bin_hz).ceil() as usize).max(lo + 1);
+    if hi + 1 >= spectrum.len() {
+        return None;
+    }
+    let (bin, magnitude) = spectrum[lo..=hi]
+        .iter()
+        .enumerate()
+        .map(|(i, m)| (lo + i, *m))
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    let is_peak = magnitude >= spectrum[bin - 1] && magnitude >= spectrum[bin + 1];
+    Some((bin, if is_peak { magnitude } else { 0.0 }))
+}
@@ -0,0 +1,187 @@
+use super::pitch::PitchTrack;
+use crate::{notes::NoteEvent, tuning::Tuning};
+
+/// Post-processing of note events that splits them where the onset detection function
+/// has no peak: on slurred pitch changes and on re-articulations of the same pitch.
+#[derive(Debug, Clone)]
+pub struct NoteSplitter {
+    /// Neither part of a split note may be shorter than this, in seconds
+    pub min_duration: f32,
+    /// Frames with a lower voicing probability are ignored when following the pitch
+    pub min_voiced_prob: f32,
+    /// Length in frames of the median filter applied to the pitch before splitting
+    pub smoothing: usize,
+    /// An energy minimum is a re-articulation when it falls below this fraction of the
+    /// peaks on both sides
+    pub dip_ratio: f32,
+}
+
+impl Default for NoteSplitter {
+    fn default() -> Self {
+        NoteSplitter {
+            min_duration: 0.08,
+            min_voiced_prob: 0.5,
+            smoothing: 5,
+            dip_ratio: 0.5,
+        }
+    }
+}
+
+impl NoteSplitter {
+    pub fn new(min_duration: f32) -> Self {
+        NoteSplitter {
+            min_duration,
+            ..Default::default()
+        }
+    }
+
+    /// Splits every event where the pitch track settles on a different note for at
+    /// least `min_duration` seconds
+    pub fn split_legato(
+        &self,
+        events: &[NoteEvent],
+        track: &PitchTrack,
+        tuning: &Tuning,
+    ) -> Vec<NoteEvent> {
+        let f0 = track.f0(self.min_voiced_prob);
+        let min_frames = ((self.min_duration * track.frame_rate()).ceil() as usize).max(1);
+        let mut res = Vec::with_capacity(events.len());
+
+        for event in events {
+            let first = track.frame_at(event.onset);
+            let end = track.frame_at(event.offset).max(first + 1).min(f0.len());
+            let runs = match self.pitch_runs(&f0[first.min(end)..end], tuning, min_frames) {
+                Some(runs) if runs.len() > 1 => runs,
+                _ => {
+                    res.push(*event);
+                    continue;
+                }
+            };
+
+            for (j, &(start, _)) in runs.iter().enumerate() {
+                let stop = runs.get(j + 1).map_or(end - first, |r| r.0);
+                let onset = match j {
+                    0 => event.onset,
+                    _ => track.frames[first + start].time,
+                };
+                let offset = match runs.get(j + 1) {
+                    Some(next) => track.frames[first + next.0].time,
+                    None => event.offset,
+                };
+
+                let mut voiced: Vec<f32> = f0[first + start..first + stop]
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .collect();
+                voiced.sort_by(|a, b| a.total_cmp(b));
+                let note = voiced
+                    .get(voiced.len() / 2)
+                    .map_or(event.note, |f| tuning.note(*f));
+                res.push(NoteEvent::new(onset, offset, note));
+            }
+        }
+
+        res
+    }
+
+    /// Splits every event at the minima of `energy` (e.g. an RMS envelope sampled at
+    /// `energy_rate` frames per second) deep enough to be a re-articulation
+    pub fn split_repeated(
+        &self,
+        events: &[NoteEvent],
+        energy: &[f32],
+        energy_rate: f32,
+    ) -> Vec<NoteEvent> {
+        let mut res = Vec::with_capacity(events.len());
+
+        for event in events {
+            let first = ((event.onset * energy_rate).ceil() as usize).min(energy.len());
+            let end = ((event.offset * energy_rate).floor() as usize).clamp(first, energy.len());
+            let mut onset = event.onset;
+
+            // Maximum of the energy from every frame to the end of the event
+            let mut after = energy[first..end].to_vec();
+            for j in (1..after.len()).rev() {
+                after[j - 1] = after[j - 1].max(after[j]);
+            }
+
+            let mut before = 0.0f32;
+            let mut dip: Option<usize> = None;
+            for j in first..end {
+                before = before.max(energy[j]);
+                if energy[j] < self.dip_ratio * before.min(after[j - first]) {
+                    if dip.is_none_or(|d| energy[j] < energy[d]) {
+                        dip = Some(j);
+                    }
+                    continue;
+                }
+
+                // Split at the bottom of the dip once the energy has recovered
+                let Some(d) = dip.take() else { continue };
+                let time = d as f32 / energy_rate;
+                if time - onset >= self.min_duration && event.offset - time >= self.min_duration {
+                    res.push(NoteEvent::new(onset, time, event.note));
+                    onset = time;
+                    before = energy[j];
+                }
+            }
+
+            res.push(NoteEvent::new(onset, event.offset, event.note));
+        }
+
+        res
+    }
+
+    /// Start frame and semitone of every stable pitch in `f0`, after median filtering.
+    /// Runs shorter than `min_frames` are absorbed by their neighbours. `None` if no
+    /// frame is voiced.
+    fn pitch_runs(
+        &self,
+        f0: &[Option<f32>],
+        tuning: &Tuning,
+        min_frames: usize,
+    ) -> Option<Vec<(usize, i32)>> {
+        // Unvoiced frames inherit the pitch of the previous voiced one
+        let leading = f0.iter().flatten().next()?;
+        let mut current = tuning.note(*leading).semitones();
+        let semitones: Vec<i32> = f0
+            .iter()
+            .map(|f| {
+                if let Some(f) = f {
+                    current = tuning.note(*f).semitones();
+                }
+                current
+            })
+            .collect();
+
+        let half = self.smoothing / 2;
+        let smoothed: Vec<i32> = (0..semitones.len())
+            .map(|i| {
+                let mut window =
+                    semitones[i.saturating_sub(half)..(i + half + 1).min(semitones.len())].to_vec();
+                window.sort();
+                window[window.len() / 2]
+            })
+            .collect();
+
+        let mut runs: Vec<(usize, usize, i32)> = Vec::new();
+        for (i, s) in smoothed.iter().enumerate() {
+            match runs.last_mut() {
+                Some(run) if run.2 == *s => run.1 = i + 1,
+                _ => runs.push((i, i + 1, *s)),
+            }
+        }
+
+        let mut merged: Vec<(usize, usize, i32)> = Vec::with_capacity(runs.len());
+        for run in runs {
+            match merged.last_mut() {
+                Some(last) if run.1 - run.0 < min_frames || last.2 == run.2 => last.1 = run.1,
+                Some(last) if last.1 - last.0 < min_frames => *last = (last.0, run.1, run.2),
+                _ => merged.push(run),
+            }
+        }
+
+        Some(merged.iter().map(|r| (r.0, r.2)).collect())
+    }
+}
@@ -0,0 +1,265 @@
+use super::{
+    pitch::PitchTrack,
+    shared::{OdfTiming, standardize},
+};
+use crate::{notes::NoteEvent, tuning::Tuning};
+
+/// Probability of staying in the attack state of a note for one more frame
+const ATTACK_STAY: f32 = 0.5;
+/// Slope of the logistic mapping standardized ODF values to onset probabilities
+const ONSET_SLOPE: f32 = 2.0;
+/// Lower bound on probabilities, so that their logarithms stay finite
+const FLOOR: f32 = 1e-6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Attack,
+    Sustain,
+    Silence,
+}
+
+const PHASES: [Phase; 3] = [Phase::Attack, Phase::Sustain, Phase::Silence];
+
+/// Note segmentation with an HMM over attack, sustain and silence states of every
+/// semitone, in the spirit of the pYIN note tracker (Mauch et al., 2015). The ODF
+/// sets the probability of leaving a sustained note for a new attack, so that repeated
+/// notes split on strong onsets.
+#[derive(Debug, Clone)]
+pub struct NoteTracker {
+    /// Minimum note frequency in Hz
+    pub fmin: f32,
+    /// Maximum note frequency in Hz
+    pub fmax: f32,
+    /// Standard deviation in semitones of the f0 around the note being played
+    pub pitch_sigma: f32,
+    /// Standard deviation in semitones of the interval between consecutive notes
+    pub jump_sigma: f32,
+    /// Probability of a sustained note being released in a given frame
+    pub release_prob: f32,
+    /// Probability of staying silent for one more frame
+    pub silence_prob: f32,
+    /// Standardized ODF value at which an onset is as likely as not
+    pub onset_threshold: f32,
+    /// Notes shorter than this, in seconds, are dropped
+    pub min_duration: f32,
+}
+
+impl Default for NoteTracker {
+    fn default() -> Self {
+        NoteTracker {
+            fmin: 40.0,
+            fmax: 1200.0,
+            pitch_sigma: 0.5,
+            jump_sigma: 12.0,
+            release_prob: 0.01,
+            silence_prob: 0.98,
+            onset_threshold: 2.0,
+            min_duration: 0.05,
+        }
+    }
+}
+
+impl NoteTracker {
+    pub fn new(fmin: f32, fmax: f32) -> Self {
+        NoteTracker {
+            fmin,
+            fmax,
+            ..Default::default()
+        }
+    }
+
+    /// Segments `track` into notes, using `odf` (with the time base `timing`) to find
+    /// the onsets of repeated notes
+    pub fn track(
+        &self,
+        track: &PitchTrack,
+        odf: &[f32],
+        timing: OdfTiming,
+        tuning: &Tuning,
+    ) -> Vec<NoteEvent> {
+        let lowest = tuning.semitones(self.fmin).round() as i32;
+        let highest = tuning.semitones(self.fmax).round() as i32;
+        let pitches = (highest - lowest + 1).max(1) as usize;
+
+        let onsets = self.onset_probabilities(track, odf, timing);
+        let states = self.viterbi(track, &onsets, tuning, lowest, pitches);
+
+        let frame_duration = 1.0 / track.frame_rate();
+        let mut events = Vec::new();
+        let mut start: Option<usize> = None;
+
+        for t in 0..=states.len() {
+            let (pitch, phase) = states.get(t).map_or((0, Phase::Silence), |s| *s);
+            let attack = phase == Phase::Attack && (t == 0 || states[t - 1] != (pitch, phase));
+
+            if (phase == Phase::Silence || attack)
+                && let Some(first) = start.take()
+            {
+                let mut voiced: Vec<f32> =
+                    track.frames[first..t].iter().filter_map(|f| f.f0).collect();
+                voiced.sort_by(|a, b| a.total_cmp(b));
+                let freq = voiced
+                    .get(voiced.len() / 2)
+                    .copied()
+                    .unwrap_or_else(|| tuning.freq(lowest + states[first].0 as i32));
+
+                let onset = track.frames[first].time;
+                // The last frame stands for the half hop either side of its centre
+                let offset = track.frames[t - 1].time + frame_duration / 2.0;
+                if offset - onset >= self.min_duration {
+                    events.push(NoteEvent::new(onset, offset, tuning.note(freq)));
+                }
+            }
+            if start.is_none() && phase != Phase::Silence {
+                start = Some(t);
+            }
+        }
+
+        events
+    }
+
+    /// Probability of an onset at every frame of `track`, from the maximum of the
+    /// standardized ODF over the frame
+    fn onset_probabilities(&self, track: &PitchTrack, odf: &[f32], timing: OdfTiming) -> Vec<f32> {
+        let mut odf = odf.to_vec();
+        if odf.len() > 1 {
+            standardize(&mut odf);
+        }
+        let half_frame = 0.5 / track.frame_rate();
+
+        track
+            .frames
+            .iter()
+            .map(|frame| {
+                let from = timing.position(frame.time - half_frame).floor().max(0.0) as usize;
+                let to = timing.position(frame.time + half_frame).ceil().max(0.0) as usize;
+                let to = to.min(odf.len());
+                let peak = odf
+                    .get(from..to)
+                    .and_then(|w| w.iter().copied().reduce(f32::max))
+                    .filter(|p| p.is_finite())
+                    .unwrap_or(f32::NEG_INFINITY);
+                1.0 / (1.0 + (-ONSET_SLOPE * (peak - self.onset_threshold)).exp())
+            })
+            .collect()
+    }
+
+    /// Most likely (pitch, phase) of every frame, pitches counted from `lowest`
+    fn viterbi(
+        &self,
+        track: &PitchTrack,
+        onsets: &[f32],
+        tuning: &Tuning,
+        lowest: i32,
+        pitches: usize,
+    ) -> Vec<(usize, Phase)> {
+        let index = |pitch: usize, phase: Phase| pitch * PHASES.len() + phase as usize;
+        let state_count = pitches * PHASES.len();
+
+        // Log-probability of arriving at a pitch (outer) from another one (inner) on a
+        // new attack
+        let weight = |from: usize, to: usize| gaussian((to as f32 - from as f32) / self.jump_sigma);
+        let totals: Vec<f32> = (0..pitches)
+            .map(|from| (0..pitches).map(|to| weight(from, to)).sum())
+            .collect();
+        let jumps: Vec<Vec<f32>> = (0..pitches)
+            .map(|to| {
+                totals
+                    .iter()
+                    .enumerate()
+                    .map(|(from, total)| (weight(from, to) / total).max(FLOOR).ln())
+                    .collect()
+            })
+            .collect();
+
+        let emissions = |t: usize| -> Vec<f32> {
+            let frame = &track.frames[t];
+            let mut emission = vec![0.0; state_count];
+            for pitch in 0..pitches {
+                let voiced = frame.f0.map_or(0.0, |f0| {
+                    let distance = tuning.semitones(f0) - (lowest + pitch as i32) as f32;
+                    frame.voiced_prob * gaussian(distance / self.pitch_sigma)
+                });
+                let silent = 1.0 - frame.voiced_prob;
+                emission[index(pitch, Phase::Attack)] = voiced.max(FLOOR).ln();
+                emission[index(pitch, Phase::Sustain)] = voiced.max(FLOOR).ln();
+                emission[index(pitch, Phase::Silence)] = silent.max(FLOOR).ln();
+            }
+            emission
+        };
+
+        if track.is_empty() {
+            return Vec::new();
+        }
+
+        let attack_stay = ATTACK_STAY.ln();
+        let attack_leave = (1.0 - ATTACK_STAY).ln();
+        let release = self.release_prob.max(FLOOR).ln();
+        let silence_stay = self.silence_prob.max(FLOOR).ln();
+        let silence_leave = (1.0 - self.silence_prob).max(FLOOR).ln();
+
+        let mut score = emissions(0);
+        let mut backpointers: Vec<Vec<usize>> = Vec::with_capacity(track.len());
+
+        for (t, onset) in onsets.iter().enumerate().skip(1) {
+            let onset = onset.clamp(FLOOR, 1.0 - FLOOR);
+            let reattack = onset.ln();
+            let sustain = ((1.0 - onset) * (1.0 - self.release_prob)).max(FLOOR).ln();
+            let emission = emissions(t);
+
+            let mut next = vec![f32::NEG_INFINITY; state_count];
+            let mut pointers = vec![0; state_count];
+            let mut relax = |to: usize, from: usize, value: f32| {
+                if value > next[to] {
+                    next[to] = value;
+                    pointers[to] = from;
+                }
+            };
+
+            for (to, incoming) in jumps.iter().enumerate() {
+                let attack = index(to, Phase::Attack);
+                relax(attack, attack, score[attack] + attack_stay);
+                for (from, jump) in incoming.iter().enumerate() {
+                    let sustained = index(from, Phase::Sustain);
+                    let silent = index(from, Phase::Silence);
+                    relax(attack, sustained, score[sustained] + reattack + jump);
+                    relax(attack, silent, score[silent] + silence_leave + jump);
+                }
+
+                let sustained = index(to, Phase::Sustain);
+                let silent = index(to, Phase::Silence);
+                relax(sustained, attack, score[attack] + attack_leave);
+                relax(sustained, sustained, score[sustained] + sustain);
+                relax(silent, sustained, score[sustained] + release);
+                relax(silent, silent, score[silent] + silence_stay);
+            }
+
+            for (n, e) in next.iter_mut().zip(emission.iter()) {
+                *n += e;
+            }
+            backpointers.push(pointers);
+            score = next;
+        }
+
+        let mut state = score
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map_or(0, |(i, _)| i);
+        let mut path = vec![state];
+        for pointers in backpointers.iter().rev() {
+            state = pointers[state];
+            path.push(state);
+        }
+        path.reverse();
+
+        path.iter()
+            .map(|s| (s / PHASES.len(), PHASES[s % PHASES.len()]))
+            .collect()
+    }
+}
+
+/// Unnormalized standard normal density
+fn gaussian(x: f32) -> f32 {
+    (-0.5 * x * x).exp()
+}
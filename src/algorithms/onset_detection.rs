@@ -1,4 +1,6 @@
-use rustfft::{FftPlanner, num_complex::Complex};
+use rustfft::num_complex::Complex;
+
+use super::shared::stft;
 
 pub struct StftBasedOnset<'a> {
     samples: &'a [f32],
@@ -19,15 +21,14 @@ impl<'a> StftBasedOnset<'a> {
     pub fn spectral_flux(&self) -> Vec<f32> {
         let stft = self.stft();
         let num_frames = stft.len();
-        let num_bins = stft[0].len();
         let mut sf = Vec::with_capacity(num_frames);
 
         for n in 1..num_frames {
             let mut sum = 0.0;
 
-            for k in 0..num_bins {
+            for (x_n, x_n1) in stft[n].iter().zip(stft[n - 1].iter()) {
                 // Magnitude difference
-                let mag_diff = stft[n][k].norm() - stft[n - 1][k].norm();
+                let mag_diff = x_n.norm() - x_n1.norm();
 
                 // Half-wave rectification
                 if mag_diff > 0.0 {
@@ -55,8 +56,7 @@ impl<'a> StftBasedOnset<'a> {
         let stft = self.stft();
 
         let num_frames = stft.len();
-        let num_bins = stft[0].len();
-        let mut cd = Vec::with_capacity(num_frames - 2);
+        let mut cd = Vec::with_capacity(num_frames.saturating_sub(2));
 
         if num_frames < 3 {
             return cd;
@@ -64,11 +64,7 @@ impl<'a> StftBasedOnset<'a> {
 
         for n in 2..num_frames {
             let mut sum = 0.0;
-            for k in 0..num_bins {
-                let x_n = stft[n][k];
-                let x_n1 = stft[n - 1][k];
-                let x_n2 = stft[n - 2][k];
-
+            for ((&x_n, &x_n1), &x_n2) in stft[n].iter().zip(&stft[n - 1]).zip(&stft[n - 2]) {
                 // Amplitude and phase of X(n-1, k)
                 let amp_n1 = x_n1.norm();
                 let phase_n1 = x_n1.arg();
@@ -92,34 +88,6 @@ impl<'a> StftBasedOnset<'a> {
 
     /// Short Term Fourier Transform
     fn stft(&self) -> Vec<Vec<Complex<f32>>> {
-        let mut stft_result = Vec::new();
-
-        let frame_size = self.frame_size;
-        let hop_size = self.hop_size;
-
-        let hw = self.hamming_window(frame_size);
-
-        let mut planner = FftPlanner::new();
-        let fft = planner.plan_fft_forward(frame_size);
-
-        for frame in self.samples.windows(frame_size).step_by(hop_size) {
-            let mut f: Vec<Complex<f32>> = frame
-                .iter()
-                .enumerate()
-                .map(|(j, &sample)| Complex::new(sample * hw[j], 0.0))
-                .collect();
-            fft.process(&mut f);
-            stft_result.push(f);
-        }
-
-        stft_result
-    }
-
-    fn hamming_window(&self, size: usize) -> Vec<f32> {
-        (0..size)
-            .map(|i| {
-                0.54 - 0.46 * (2.0 * std::f32::consts::PI * i as f32 / (size as f32 - 1.0)).cos()
-            })
-            .collect()
+        stft(self.samples, self.frame_size, self.hop_size)
     }
 }
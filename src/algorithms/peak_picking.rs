@@ -1,11 +1,20 @@
-use crate::notes::Note;
+use crate::{
+    notes::{Note, NoteEvent},
+    tuning::Tuning,
+};
 
-use super::shared::{frame_to_frames, frame_to_seconds, standardize};
+use super::{
+    pitch::PitchTrack,
+    shared::{OdfTiming, frame_to_seconds, standardize},
+};
 
-pub fn peak_picking(f: &mut Vec<f32>, w: usize, m: usize, delta: f32, alpha: f32) -> Vec<bool> {
+pub fn peak_picking(f: &mut [f32], w: usize, m: usize, delta: f32, alpha: f32) -> Vec<bool> {
     standardize(f);
     let n = f.len();
     let mut onsets = vec![false; n];
+    if n == 0 {
+        return onsets;
+    }
 
     let mut g_alpha = vec![0.0; n];
     g_alpha[0] = f[0];
@@ -40,29 +49,48 @@ pub fn peak_picking(f: &mut Vec<f32>, w: usize, m: usize, delta: f32, alpha: f32
     onsets
 }
 
+/// Time in seconds of every onset of `pp`
+pub fn peak_times(pp: &[bool], timing: OdfTiming) -> Vec<f32> {
+    pp.iter()
+        .enumerate()
+        .filter(|(_, f)| **f)
+        .map(|(i, _)| timing.time(i))
+        .collect()
+}
+
+/// Spreads the frames of `pp` evenly over `duration`, which ignores the frame centres
+/// and the lag of the ODF. Prefer [`peak_times`].
 pub fn peak_picking_to_seconds(pp: &[bool], duration: f32) -> Vec<f32> {
-    let mut frame = 0;
     let mut res = Vec::new();
 
-    for f in pp {
+    for (frame, f) in pp.iter().enumerate() {
         if *f {
             res.push(frame_to_seconds(duration, pp.len(), frame));
         }
-        frame += 1;
     }
     res
 }
 
-pub fn peak_picking_to_notes(pp: &[bool], notes: &[Option<Note>]) -> Vec<Option<Note>> {
+/// Maps every onset of `pp` (an ODF with the time base `timing`) to the first voiced
+/// note of `track` around it.
+pub fn peak_picking_to_notes(
+    pp: &[bool],
+    timing: OdfTiming,
+    track: &PitchTrack,
+    min_voiced_prob: f32,
+    tuning: &Tuning,
+) -> Vec<Option<Note>> {
+    let notes = track.notes(min_voiced_prob, tuning);
     let mut res = Vec::new();
 
     for (i, f) in pp.iter().enumerate() {
         if *f {
-            let frame = frame_to_frames(pp.len(), notes.len(), i);
+            let frame = track.frame_at(timing.time(i));
             let guess_window = 3;
             let mut note = None;
-            for i in
-                (frame.saturating_sub(guess_window - 1)..frame.saturating_add(guess_window)).rev()
+            for i in (frame.saturating_sub(guess_window - 1)
+                ..frame.saturating_add(guess_window).min(notes.len()))
+                .rev()
             {
                 if let Some(n) = notes[i] {
                     note = Some(n);
@@ -76,3 +104,48 @@ pub fn peak_picking_to_notes(pp: &[bool], notes: &[Option<Note>]) -> Vec<Option<
     }
     res
 }
+
+/// Turns every onset of `pp` into a note lasting until the next onset or the end of the
+/// voiced region that follows it, whichever comes first. Onsets without a voiced frame
+/// nearby are dropped.
+pub fn peak_picking_to_events(
+    pp: &[bool],
+    timing: OdfTiming,
+    track: &PitchTrack,
+    min_voiced_prob: f32,
+    tuning: &Tuning,
+) -> Vec<NoteEvent> {
+    let f0 = track.f0(min_voiced_prob);
+    let onsets: Vec<(f32, usize)> = pp
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| **f)
+        .map(|(i, _)| {
+            let time = timing.time(i);
+            (time, track.frame_at(time))
+        })
+        .collect();
+
+    let guess_window = 3;
+    let mut res = Vec::new();
+
+    for (j, &(time, frame)) in onsets.iter().enumerate() {
+        let next = onsets.get(j + 1).map(|o| o.1).unwrap_or(f0.len());
+        let window = frame.saturating_sub(guess_window - 1)..(frame + guess_window).min(f0.len());
+        let Some(first) = window.into_iter().find(|&i| f0[i].is_some()) else {
+            continue;
+        };
+        let last = (first..next.max(first + 1).min(f0.len()))
+            .take_while(|&i| f0[i].is_some())
+            .last()
+            .unwrap_or(first);
+
+        let mut voiced: Vec<f32> = f0[first..=last].iter().flatten().copied().collect();
+        voiced.sort_by(|a, b| a.total_cmp(b));
+        let median = voiced[voiced.len() / 2];
+
+        let offset = track.frames[last].time + 1.0 / track.frame_rate();
+        res.push(NoteEvent::new(time, offset.max(time), tuning.note(median)));
+    }
+    res
+}
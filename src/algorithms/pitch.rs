@@ -0,0 +1,75 @@
+use crate::{notes::Note, samples::Samples, tuning::Tuning};
+
+/// A single analysis frame of a pitch track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PitchFrame {
+    /// Center of the frame in seconds
+    pub time: f32,
+    /// Fundamental frequency in Hz, `None` when the frame is unvoiced
+    pub f0: Option<f32>,
+    /// Probability that the frame is voiced, in [0, 1]
+    pub voiced_prob: f32,
+}
+
+/// Frame-wise f0 estimate with a known hop, so it can be aligned with any other
+/// frame-based analysis (e.g. the onset detection function).
+#[derive(Debug, Clone)]
+pub struct PitchTrack {
+    pub sample_rate: u32,
+    pub hop_size: usize,
+    pub frames: Vec<PitchFrame>,
+}
+
+impl PitchTrack {
+    pub fn new(sample_rate: u32, hop_size: usize, frames: Vec<PitchFrame>) -> Self {
+        PitchTrack {
+            sample_rate,
+            hop_size,
+            frames,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Frames per second
+    pub fn frame_rate(&self) -> f32 {
+        self.sample_rate as f32 / self.hop_size as f32
+    }
+
+    /// Index of the frame closest to `time` (in seconds)
+    pub fn frame_at(&self, time: f32) -> usize {
+        let first = self.frames.first().map(|f| f.time).unwrap_or(0.0);
+        let frame = ((time - first) * self.frame_rate()).round().max(0.0) as usize;
+        frame.min(self.frames.len().saturating_sub(1))
+    }
+
+    /// f0 of every frame, `None` if unvoiced or below `min_voiced_prob`
+    pub fn f0(&self, min_voiced_prob: f32) -> Vec<Option<f32>> {
+        self.frames
+            .iter()
+            .map(|f| f.f0.filter(|_| f.voiced_prob >= min_voiced_prob))
+            .collect()
+    }
+
+    /// Nearest note of every frame, `None` if unvoiced or below `min_voiced_prob`
+    pub fn notes(&self, min_voiced_prob: f32, tuning: &Tuning) -> Vec<Option<Note>> {
+        self.f0(min_voiced_prob)
+            .into_iter()
+            .map(|f| f.map(|f| tuning.note(f)))
+            .collect()
+    }
+}
+
+/// Common interface of the monophonic f0 estimators.
+pub trait PitchDetector {
+    /// Distance in samples between two consecutive frames of the returned track
+    fn hop_size(&self) -> usize;
+
+    fn detect(&mut self, samples: &Samples) -> PitchTrack;
+}
@@ -1,3 +1,5 @@
+use rustfft::{FftPlanner, num_complex::Complex};
+
 pub fn standardize(data: &mut [f32]) {
     let n = data.len() as f32;
 
@@ -6,6 +8,8 @@ pub fn standardize(data: &mut [f32]) {
     let variance = data.iter().map(|&x| (x - mean).powi(2)).sum::<f32>() / n;
     let std_dev = variance.sqrt();
 
+    // A constant signal, such as silence, is only centred
+    let std_dev = if std_dev > 0.0 { std_dev } else { 1.0 };
     for x in data.iter_mut() {
         *x = (*x - mean) / std_dev;
     }
@@ -15,6 +19,164 @@ pub fn frame_to_seconds(duration: f32, frames: usize, frame: usize) -> f32 {
     (duration / frames as f32) * frame as f32
 }
 
-pub fn frame_to_frames(frames_a: usize, frames_b: usize, frame: usize) -> usize {
-    frames_b * frame / frames_a
+/// Time in seconds of value `index` of an onset detection function: the centre of the
+/// STFT frame it is computed at, `lag` frames after the first frame of the STFT
+pub fn odf_frame_time(
+    index: usize,
+    lag: usize,
+    hop_size: usize,
+    frame_size: usize,
+    sample_rate: u32,
+) -> f32 {
+    ((index + lag) * hop_size + frame_size / 2) as f32 / sample_rate as f32
+}
+
+/// Time base of an onset detection function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OdfTiming {
+    /// STFT frames consumed before the first ODF value
+    pub lag: usize,
+    pub hop_size: usize,
+    pub frame_size: usize,
+    pub sample_rate: u32,
+}
+
+impl OdfTiming {
+    /// Frames per second
+    pub fn rate(&self) -> f32 {
+        self.sample_rate as f32 / self.hop_size as f32
+    }
+
+    /// Time in seconds of ODF value `index`
+    pub fn time(&self, index: usize) -> f32 {
+        odf_frame_time(
+            index,
+            self.lag,
+            self.hop_size,
+            self.frame_size,
+            self.sample_rate,
+        )
+    }
+
+    /// Fractional ODF index at `time` seconds, negative before the first value
+    pub fn position(&self, time: f32) -> f32 {
+        (time - self.time(0)) * self.rate()
+    }
+}
+
+/// Short Term Fourier Transform with a Hamming window
+pub fn stft(samples: &[f32], frame_size: usize, hop_size: usize) -> Vec<Vec<Complex<f32>>> {
+    padded_stft(samples, frame_size, hop_size, frame_size)
+}
+
+/// Short Term Fourier Transform where every windowed frame is zero-padded to `fft_size`
+pub fn padded_stft(
+    samples: &[f32],
+    frame_size: usize,
+    hop_size: usize,
+    fft_size: usize,
+) -> Vec<Vec<Complex<f32>>> {
+    let mut stft_result = Vec::new();
+
+    let hw = hamming_window(frame_size);
+
+    let mut planner = FftPlanner::new();
+    let fft = planner.plan_fft_forward(fft_size.max(frame_size));
+
+    for frame in samples.windows(frame_size).step_by(hop_size) {
+        let mut f: Vec<Complex<f32>> = frame
+            .iter()
+            .enumerate()
+            .map(|(j, &sample)| Complex::new(sample * hw[j], 0.0))
+            .collect();
+        f.resize(fft_size.max(frame_size), Complex::new(0.0, 0.0));
+        fft.process(&mut f);
+        stft_result.push(f);
+    }
+
+    stft_result
+}
+
+pub fn hamming_window(size: usize) -> Vec<f32> {
+    (0..size)
+        .map(|i| 0.54 - 0.46 * (2.0 * std::f32::consts::PI * i as f32 / (size as f32 - 1.0)).cos())
+        .collect()
+}
+
+/// Fractional offset in (-0.5, 0.5) of the true maximum around `y[i]`, fitting a parabola
+/// through the peak and its two neighbours
+pub fn parabolic_offset(y: &[f32], i: usize) -> f32 {
+    if i == 0 || i + 1 >= y.len() {
+        return 0.0;
+    }
+    let (a, b, c) = (y[i - 1], y[i], y[i + 1]);
+    let denom = a - 2.0 * b + c;
+    if denom.abs() < f32::EPSILON {
+        0.0
+    } else {
+        (0.5 * (a - c) / denom).clamp(-0.5, 0.5)
+    }
+}
+
+/// RMS of every frame, with the signal zero-padded so that frame `i` is centered at
+/// `i * hop_size` samples
+pub fn rms_envelope(samples: &[f32], frame_size: usize, hop_size: usize) -> Vec<f32> {
+    let mut padded = vec![0.0; frame_size / 2];
+    padded.extend_from_slice(samples);
+    padded.resize(padded.len() + frame_size / 2, 0.0);
+
+    padded
+        .windows(frame_size)
+        .step_by(hop_size)
+        .map(|frame| (frame.iter().map(|s| s * s).sum::<f32>() / frame_size as f32).sqrt())
+        .collect()
+}
+
+/// xorshift64* pseudo-random generator, for reproducible noise and sampling without
+/// a dependency
+#[derive(Debug, Clone)]
+pub struct XorShift(u64);
+
+impl XorShift {
+    /// Generator seeded through splitmix64, which maps every seed, including 0, to a
+    /// non-zero state
+    pub fn new(seed: u64) -> Self {
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        // splitmix64 is a bijection, so exactly one seed lands on the forbidden state
+        XorShift(if z == 0 { 0x9E37_79B9_7F4A_7C15 } else { z })
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform value in [-1, 1)
+    pub fn next_signed(&mut self) -> f32 {
+        let value = self.next_u64() >> 40;
+        value as f32 / (1u64 << 23) as f32 - 1.0
+    }
+
+    /// Uniform index in 0..len, `len` must not be 0
+    pub fn next_index(&mut self, len: usize) -> usize {
+        (self.next_u64() % len as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xorshift_never_starts_at_zero() {
+        for seed in [0, 0x9E37_79B9_7F4A_7C15, u64::MAX] {
+            let mut rng = XorShift::new(seed);
+            assert!((0..4).any(|_| rng.next_u64() != 0), "seed {:#x}", seed);
+        }
+    }
 }
@@ -0,0 +1,178 @@
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+
+use super::{
+    pitch::{PitchDetector, PitchFrame, PitchTrack},
+    shared::{padded_stft, parabolic_offset},
+};
+use crate::samples::Samples;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpectralMethod {
+    /// Product of the magnitude spectrum downsampled by 1..=harmonics
+    HarmonicProduct,
+    /// Sum of the magnitude spectrum downsampled by 1..=harmonics
+    HarmonicSum,
+    /// Peak of the real cepstrum in the quefrency range [1/fmax, 1/fmin]
+    Cepstrum,
+}
+
+/// Frequency-domain f0 estimator. More robust than YIN on low notes with strong
+/// harmonics, where the fundamental itself can be weaker than its overtones.
+#[derive(Debug, Clone)]
+pub struct SpectralPitch {
+    pub method: SpectralMethod,
+    pub frame_size: usize,
+    pub hop_size: usize,
+    /// Frames are zero-padded to `frame_size * zero_padding` before the FFT
+    pub zero_padding: usize,
+    /// Number of harmonics used by the harmonic product/sum
+    pub harmonics: usize,
+    /// Minimum frequency in Hz
+    pub fmin: f32,
+    /// Maximum frequency in Hz
+    pub fmax: f32,
+    /// Frames with a lower RMS are reported as unvoiced
+    pub power_threshold: f32,
+}
+
+impl SpectralPitch {
+    pub fn new(method: SpectralMethod, frame_size: usize, hop_size: usize) -> Self {
+        SpectralPitch {
+            method,
+            frame_size,
+            hop_size,
+            ..Default::default()
+        }
+    }
+
+    /// Salience of every candidate within [fmin, fmax], along with the bin (or quefrency)
+    /// of the first candidate and the width of a bin in Hz. `inverse` is the inverse FFT
+    /// of the spectrum size, used by the cepstrum.
+    fn salience(
+        &self,
+        spectrum: &[Complex<f32>],
+        sample_rate: f32,
+        inverse: &dyn Fft<f32>,
+    ) -> (Vec<f32>, usize, f32) {
+        let fft_size = spectrum.len();
+        let bin_hz = sample_rate / fft_size as f32;
+        let half = fft_size / 2;
+
+        let max_mag = spectrum[..half]
+            .iter()
+            .map(|c| c.norm())
+            .fold(f32::EPSILON, f32::max);
+        let mag: Vec<f32> = spectrum[..half]
+            .iter()
+            .map(|c| c.norm() / max_mag)
+            .collect();
+
+        match self.method {
+            SpectralMethod::HarmonicProduct | SpectralMethod::HarmonicSum => {
+                let lo = ((self.fmin / bin_hz).floor() as usize).max(1);
+                let hi = ((self.fmax / bin_hz).ceil() as usize).min(half / self.harmonics.max(1));
+                let salience = (lo..=hi.max(lo))
+                    .map(|k| {
+                        let harmonics = (1..=self.harmonics).map(|h| mag.get(h * k).copied());
+                        if self.method == SpectralMethod::HarmonicProduct {
+                            harmonics.map(|m| m.unwrap_or(0.0)).product()
+                        } else {
+                            harmonics.map(|m| m.unwrap_or(0.0)).sum()
+                        }
+                    })
+                    .collect();
+                (salience, lo, bin_hz)
+            }
+            SpectralMethod::Cepstrum => {
+                let mut log_spectrum: Vec<Complex<f32>> = spectrum
+                    .iter()
+                    .map(|c| Complex::new((c.norm() / max_mag + 1e-6).ln(), 0.0))
+                    .collect();
+                inverse.process(&mut log_spectrum);
+
+                let lo = ((sample_rate / self.fmax).floor() as usize).max(1);
+                let hi = ((sample_rate / self.fmin).ceil() as usize).min(self.frame_size / 2);
+                let salience = (lo..=hi.max(lo))
+                    .map(|q| log_spectrum[q].re / fft_size as f32)
+                    .collect();
+                (salience, lo, 1.0)
+            }
+        }
+    }
+}
+
+impl Default for SpectralPitch {
+    fn default() -> Self {
+        SpectralPitch {
+            method: SpectralMethod::HarmonicProduct,
+            frame_size: 4096,
+            hop_size: 512,
+            zero_padding: 4,
+            harmonics: 5,
+            fmin: 30.0,
+            fmax: 600.0,
+            power_threshold: 1e-3,
+        }
+    }
+}
+
+impl PitchDetector for SpectralPitch {
+    fn hop_size(&self) -> usize {
+        self.hop_size
+    }
+
+    fn detect(&mut self, samples: &Samples) -> PitchTrack {
+        let sample_rate = samples.spec.sample_rate;
+        let sr = sample_rate as f32;
+        let fft_size = self.frame_size * self.zero_padding.max(1);
+        let stft = padded_stft(samples, self.frame_size, self.hop_size, fft_size);
+        let inverse = FftPlanner::new().plan_fft_inverse(fft_size);
+
+        let frames = stft
+            .iter()
+            .enumerate()
+            .map(|(i, spectrum)| {
+                let start = i * self.hop_size;
+                let time = (start + self.frame_size / 2) as f32 / sr;
+                let frame = &samples[start..start + self.frame_size];
+                let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt();
+                if rms < self.power_threshold {
+                    return PitchFrame {
+                        time,
+                        f0: None,
+                        voiced_prob: 0.0,
+                    };
+                }
+
+                let (salience, offset, scale) = self.salience(spectrum, sr, inverse.as_ref());
+                let (peak, &max) = salience
+                    .iter()
+                    .enumerate()
+                    .max_by(|a, b| a.1.total_cmp(b.1))
+                    .unwrap();
+                let min = salience.iter().copied().fold(f32::INFINITY, f32::min);
+                let mean = salience.iter().sum::<f32>() / salience.len() as f32;
+                // How much the winning candidate stands out from the rest
+                let voiced_prob = if max > min {
+                    (1.0 - (mean - min) / (max - min)).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+
+                let index = (offset + peak) as f32 + parabolic_offset(&salience, peak);
+                let f0 = match self.method {
+                    SpectralMethod::Cepstrum => sr / index,
+                    _ => index * scale,
+                };
+
+                PitchFrame {
+                    time,
+                    f0: Some(f0),
+                    voiced_prob,
+                }
+            })
+            .collect();
+
+        PitchTrack::new(sample_rate, self.hop_size, frames)
+    }
+}
@@ -1,42 +1,142 @@
-use crate::{notes::Note, samples::Samples};
-use pitch_detection::detector::{PitchDetector, yin::YINDetector};
-use std::ops::Deref;
+use super::pitch::{PitchDetector, PitchFrame, PitchTrack};
+use crate::samples::Samples;
+use pitch_detection::detector::{PitchDetector as _, yin::YINDetector};
+use pyin::{Framing, PYINExecutor, PadMode};
 
-#[derive(Debug)]
-pub struct Yin(Vec<Option<Note>>);
+/// YIN estimator backed by the `pitch-detection` crate
+#[derive(Debug, Clone)]
+pub struct Yin {
+    pub frame_size: usize,
+    pub hop_size: usize,
+    pub power_threshold: f64,
+    pub clarity_threshold: f64,
+}
 
-impl From<Samples> for Yin {
-    fn from(samples: Samples) -> Self {
-        const SAMPLE_RATE: usize = 44100;
+impl Yin {
+    pub fn new(frame_size: usize, hop_size: usize) -> Self {
+        Yin {
+            frame_size,
+            hop_size,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for Yin {
+    fn default() -> Self {
         const SIZE: usize = 1024 * 5;
-        const PADDING: usize = SIZE / 2;
-        const POWER_THRESHOLD: f64 = 1.0;
-        const CLARITY_THRESHOLD: f64 = 0.8;
+        Yin {
+            frame_size: SIZE,
+            hop_size: SIZE,
+            power_threshold: 1.0,
+            clarity_threshold: 0.8,
+        }
+    }
+}
+
+impl PitchDetector for Yin {
+    fn hop_size(&self) -> usize {
+        self.hop_size
+    }
 
+    fn detect(&mut self, samples: &Samples) -> PitchTrack {
+        let sample_rate = samples.spec.sample_rate;
         let signal: Vec<f64> = samples.iter().map(|s| *s as f64).collect();
-        let mut detector = YINDetector::new(SIZE, PADDING);
-
-        let mut notes = Vec::new();
-
-        for i in 0..signal.len() / SIZE {
-            let samples = &signal[i * SIZE..(i + 1) * SIZE];
-            match detector.get_pitch(&samples, SAMPLE_RATE, POWER_THRESHOLD, CLARITY_THRESHOLD) {
-                Some(pitch) => {
-                    notes.push(Some(Note::from(pitch.frequency as f32)));
-                }
-                None => {
-                    notes.push(None);
-                }
-            }
+        let mut detector = YINDetector::new(self.frame_size, self.frame_size / 2);
+
+        let mut frames = Vec::new();
+
+        for (i, frame) in signal
+            .windows(self.frame_size)
+            .step_by(self.hop_size)
+            .enumerate()
+        {
+            let time = (i * self.hop_size + self.frame_size / 2) as f32 / sample_rate as f32;
+            let pitch = detector.get_pitch(
+                frame,
+                sample_rate as usize,
+                self.power_threshold,
+                self.clarity_threshold,
+            );
+            frames.push(match pitch {
+                Some(pitch) => PitchFrame {
+                    time,
+                    f0: Some(pitch.frequency as f32),
+                    voiced_prob: pitch.clarity as f32,
+                },
+                None => PitchFrame {
+                    time,
+                    f0: None,
+                    voiced_prob: 0.0,
+                },
+            });
         }
-        Yin(notes)
+        PitchTrack::new(sample_rate, self.hop_size, frames)
     }
 }
 
-impl Deref for Yin {
-    type Target = [Option<Note>];
+/// Probabilistic YIN estimator backed by the `pyin` crate
+#[derive(Debug, Clone)]
+pub struct PYin {
+    /// Minimum frequency in Hz
+    pub fmin: f64,
+    /// Maximum frequency in Hz
+    pub fmax: f64,
+    pub frame_length: usize,
+    pub win_length: usize,
+    pub hop_length: usize,
+    pub resolution: f64,
+}
+
+impl PYin {
+    pub fn new(fmin: f64, fmax: f64, frame_length: usize) -> Self {
+        PYin {
+            fmin,
+            fmax,
+            frame_length,
+            win_length: frame_length / 2,
+            hop_length: frame_length / 4,
+            resolution: 0.05,
+        }
+    }
+}
+
+impl Default for PYin {
+    fn default() -> Self {
+        PYin::new(40.0, 600.0, 4096)
+    }
+}
+
+impl PitchDetector for PYin {
+    fn hop_size(&self) -> usize {
+        self.hop_length
+    }
+
+    fn detect(&mut self, samples: &Samples) -> PitchTrack {
+        let sample_rate = samples.spec.sample_rate;
+        let mut pyin_exec = PYINExecutor::new(
+            self.fmin,
+            self.fmax,
+            sample_rate,
+            self.frame_length,
+            Some(self.win_length),
+            Some(self.hop_length),
+            Some(self.resolution),
+        );
+
+        let wav: Vec<f64> = samples.iter().map(|f| *f as f64).collect();
+        // Zero-padding is applied on both sides, so frame i is centered at i * hop
+        let framing = Framing::Center(PadMode::Constant(0.));
+
+        let (timestamp, f0, _voiced_flag, voiced_prob) = pyin_exec.pyin(&wav, f64::NAN, framing);
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+        let frames = (0..f0.len())
+            .map(|i| PitchFrame {
+                time: timestamp[i] as f32,
+                f0: Some(f0[i] as f32).filter(|f| !f.is_nan()),
+                voiced_prob: voiced_prob[i] as f32,
+            })
+            .collect();
+        PitchTrack::new(sample_rate, self.hop_length, frames)
     }
 }
@@ -0,0 +1,451 @@
+use std::{collections::HashMap, error::Error, fmt, fs, path::Path, str::FromStr};
+
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+use crate::{
+    notes::{Note, NoteEvent},
+    tuning::Tuning,
+};
+
+/// MIDI channel reserved for percussion (10, zero-based), skipped when reading notes
+const PERCUSSION_CHANNEL: u8 = 9;
+
+/// Tempo of a MIDI file until its first tempo event, in microseconds per beat
+const DEFAULT_MIDI_TEMPO: f32 = 500_000.0;
+
+/// Ground truth of a recording
+#[derive(Debug, Clone, PartialEq)]
+pub enum Annotation {
+    /// Onset times in seconds
+    Onsets(Vec<f32>),
+    Notes(Vec<NoteEvent>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationFormat {
+    /// Standard MIDI file
+    Midi,
+    /// One onset time per line
+    Onsets,
+    /// MIREX note list: onset, offset and frequency in Hz per line
+    Mirex,
+    /// CSV export of a Sonic Visualiser layer: instants, regions or notes
+    SonicVisualiser,
+    /// Audacity label track: start, end and label per line, separated by tabs
+    Audacity,
+}
+
+/// A time instant or region with a text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub start: f32,
+    /// End of a region, `None` for an instant
+    pub end: Option<f32>,
+    pub text: String,
+    /// Frequency in Hz, the value of a Sonic Visualiser note
+    pub pitch: Option<f32>,
+}
+
+impl Annotation {
+    /// Reads an annotation file, detecting its format from the extension and the
+    /// content if `format` is `None`
+    pub fn from_file(
+        path: &Path,
+        format: Option<AnnotationFormat>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let format = format.or_else(|| AnnotationFormat::from_extension(path));
+        if format == Some(AnnotationFormat::Midi) {
+            return Ok(Annotation::Notes(read_midi(path)?));
+        }
+
+        let text = fs::read_to_string(path)?;
+        let format = format.unwrap_or_else(|| AnnotationFormat::detect(&text));
+        let annotation = match format {
+            AnnotationFormat::Midi => unreachable!("MIDI files are read as binary"),
+            AnnotationFormat::Onsets => Annotation::Onsets(parse_onsets(&text)?),
+            AnnotationFormat::Mirex => Annotation::Notes(parse_mirex_notes(&text)?),
+            AnnotationFormat::SonicVisualiser => {
+                Annotation::from_labels(parse_sonic_visualiser(&text)?)
+            }
+            AnnotationFormat::Audacity => Annotation::from_labels(parse_audacity_labels(&text)?),
+        };
+        Ok(annotation)
+    }
+
+    /// Notes if every label spans a region and has a pitch or is named after a note
+    /// such as "C#4", onsets at the start of every label otherwise
+    pub fn from_labels(labels: Vec<Label>) -> Self {
+        match labels_to_notes(&labels) {
+            Some(notes) if !notes.is_empty() => Annotation::Notes(notes),
+            _ => Annotation::Onsets(labels.iter().map(|l| l.start).collect()),
+        }
+    }
+
+    /// Onset times in seconds, those of the notes for a note annotation
+    pub fn onsets(&self) -> Vec<f32> {
+        match self {
+            Annotation::Onsets(onsets) => onsets.clone(),
+            Annotation::Notes(notes) => notes.iter().map(|n| n.onset).collect(),
+        }
+    }
+
+    pub fn notes(&self) -> Option<&[NoteEvent]> {
+        match self {
+            Annotation::Onsets(_) => None,
+            Annotation::Notes(notes) => Some(notes),
+        }
+    }
+}
+
+impl AnnotationFormat {
+    pub const ALL: [AnnotationFormat; 5] = [
+        AnnotationFormat::Midi,
+        AnnotationFormat::Onsets,
+        AnnotationFormat::Mirex,
+        AnnotationFormat::SonicVisualiser,
+        AnnotationFormat::Audacity,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnnotationFormat::Midi => "midi",
+            AnnotationFormat::Onsets => "onsets",
+            AnnotationFormat::Mirex => "mirex",
+            AnnotationFormat::SonicVisualiser => "sonic-visualiser",
+            AnnotationFormat::Audacity => "audacity",
+        }
+    }
+
+    /// Format implied by the extension of `path`, `None` for generic text files
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "mid" | "midi" => Some(AnnotationFormat::Midi),
+            "csv" => Some(AnnotationFormat::SonicVisualiser),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format of a text annotation from the layout of its first line:
+    /// - two times and a third field separated by tabs: an Audacity label track if the
+    ///   field is not a number or the times have the six decimals Audacity writes, a
+    ///   MIREX note list otherwise
+    /// - a single number: an onset list
+    /// - three numbers: a MIREX note list
+    /// - any other comma-separated fields: a Sonic Visualiser export
+    /// - anything else: an Audacity label track
+    pub fn detect(text: &str) -> Self {
+        let first = text
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#'))
+            .unwrap_or("");
+        let is_number = |field: &str| field.parse::<f32>().is_ok();
+
+        let tabs: Vec<&str> = first.split('\t').map(str::trim).collect();
+        if let &[start, end, label] = tabs.as_slice()
+            && is_number(start)
+            && is_number(end)
+        {
+            // A MIREX file written with six decimals has them in its frequencies too
+            let six_decimals = |f: &str| f.split_once('.').is_some_and(|(_, d)| d.len() == 6);
+            let audacity_times = six_decimals(start) && six_decimals(end);
+            return if !is_number(label) || (audacity_times && !six_decimals(label)) {
+                AnnotationFormat::Audacity
+            } else {
+                AnnotationFormat::Mirex
+            };
+        }
+
+        let fields = split_fields(first);
+        // Sonic Visualiser writes the label column even when it is empty
+        let columns = first.split(',').count();
+        match (fields.len(), fields.iter().all(|f| is_number(f))) {
+            (1, true) => AnnotationFormat::Onsets,
+            (3, true) if columns <= 3 => AnnotationFormat::Mirex,
+            _ if columns > 1 => AnnotationFormat::SonicVisualiser,
+            _ => AnnotationFormat::Audacity,
+        }
+    }
+}
+
+impl fmt::Display for AnnotationFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for AnnotationFormat {
+    type Err = ParseAnnotationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AnnotationFormat::ALL
+            .into_iter()
+            .find(|f| f.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| ParseAnnotationError(format!("unknown format {:?}", s)))
+    }
+}
+
+/// Notes of every track of a standard MIDI file, following its tempo changes. Notes
+/// on the percussion channel are skipped.
+pub fn read_midi(path: &Path) -> Result<Vec<NoteEvent>, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    Ok(parse_midi(&bytes)?)
+}
+
+pub fn parse_midi(bytes: &[u8]) -> Result<Vec<NoteEvent>, midly::Error> {
+    let smf = Smf::parse(bytes)?;
+
+    // Events of all tracks by absolute tick, so that the tempo map of the first track
+    // applies to the others
+    let mut events = Vec::new();
+    for track in smf.tracks.iter() {
+        let mut tick = 0u64;
+        for event in track.iter() {
+            tick += event.delta.as_int() as u64;
+            events.push((tick, event.kind));
+        }
+    }
+    events.sort_by_key(|(tick, _)| *tick);
+
+    let mut seconds_per_tick = match smf.header.timing {
+        Timing::Metrical(ticks_per_beat) => {
+            tick_duration(DEFAULT_MIDI_TEMPO, ticks_per_beat.as_int())
+        }
+        Timing::Timecode(fps, subframes) => 1.0 / (fps.as_f32() * subframes as f32),
+    };
+    let (mut last_tick, mut time) = (0u64, 0.0f32);
+    let mut sounding: HashMap<(u8, u8), Vec<f32>> = HashMap::new();
+    let mut notes = Vec::new();
+
+    for (tick, kind) in events {
+        time += (tick - last_tick) as f32 * seconds_per_tick;
+        last_tick = tick;
+        match kind {
+            TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                if let Timing::Metrical(ticks_per_beat) = smf.header.timing {
+                    seconds_per_tick =
+                        tick_duration(tempo.as_int() as f32, ticks_per_beat.as_int());
+                }
+            }
+            TrackEventKind::Midi { channel, message } => {
+                let channel = channel.as_int();
+                if channel == PERCUSSION_CHANNEL {
+                    continue;
+                }
+                let (key, on) = match message {
+                    MidiMessage::NoteOn { key, vel } => (key.as_int(), vel.as_int() > 0),
+                    MidiMessage::NoteOff { key, .. } => (key.as_int(), false),
+                    _ => continue,
+                };
+                let onsets = sounding.entry((channel, key)).or_default();
+                if on {
+                    onsets.push(time);
+                } else if !onsets.is_empty() {
+                    let onset = onsets.remove(0);
+                    if let Ok(note) = Note::try_from(key) {
+                        notes.push(NoteEvent::new(onset, time, note));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    notes.sort_by(|a, b| a.onset.total_cmp(&b.onset));
+    Ok(notes)
+}
+
+/// Seconds per tick at `tempo` microseconds per beat
+fn tick_duration(tempo: f32, ticks_per_beat: u16) -> f32 {
+    tempo / 1e6 / ticks_per_beat as f32
+}
+
+/// One onset time in seconds per line, further columns are ignored
+pub fn parse_onsets(text: &str) -> Result<Vec<f32>, ParseAnnotationError> {
+    let mut onsets = data_lines(text)
+        .map(|(n, fields)| parse_time(n, fields[0]))
+        .collect::<Result<Vec<f32>, _>>()?;
+    onsets.sort_by(f32::total_cmp);
+    Ok(onsets)
+}
+
+/// MIREX note list: onset and offset in seconds and frequency in Hz per line. The
+/// notes keep the annotated frequency.
+pub fn parse_mirex_notes(text: &str) -> Result<Vec<NoteEvent>, ParseAnnotationError> {
+    let mut notes = Vec::new();
+    for (n, fields) in data_lines(text) {
+        if fields.len() < 3 {
+            return Err(ParseAnnotationError(format!(
+                "line {}: expected onset, offset and frequency",
+                n
+            )));
+        }
+        let onset = parse_time(n, fields[0])?;
+        let offset = parse_time(n, fields[1])?;
+        let freq: f32 = fields[2]
+            .parse()
+            .ok()
+            .filter(|f: &f32| *f > 0.0)
+            .ok_or_else(|| ParseAnnotationError(format!("line {}: invalid frequency", n)))?;
+        let note = Note {
+            freq,
+            ..Tuning::default().note(freq)
+        };
+        notes.push(NoteEvent::new(onset, offset, note));
+    }
+    notes.sort_by(|a, b| a.onset.total_cmp(&b.onset));
+    Ok(notes)
+}
+
+/// Audacity label track. Point labels, whose end equals their start, have no end.
+/// The frequency ranges of spectral labels are ignored.
+pub fn parse_audacity_labels(text: &str) -> Result<Vec<Label>, ParseAnnotationError> {
+    let mut labels = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('\\') {
+            continue;
+        }
+        let mut fields = line.splitn(3, '\t');
+        let start = parse_time(n + 1, fields.next().unwrap_or("").trim())?;
+        let end = match fields.next() {
+            Some(end) => parse_time(n + 1, end.trim())?,
+            None => start,
+        };
+        labels.push(Label {
+            start,
+            end: (end > start).then_some(end),
+            text: fields.next().unwrap_or("").trim().to_string(),
+            pitch: None,
+        });
+    }
+    Ok(labels)
+}
+
+/// CSV export of a Sonic Visualiser layer: a time followed by an optional value, an
+/// optional duration and a label. Rows with three numbers are regions, rows with four,
+/// the last being the level, are notes whose value is their frequency in Hz.
+pub fn parse_sonic_visualiser(text: &str) -> Result<Vec<Label>, ParseAnnotationError> {
+    let mut labels = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let fields: Vec<&str> = line
+            .split(',')
+            .map(|f| f.trim().trim_matches('"'))
+            .collect();
+        if line.trim().is_empty() || (n == 0 && fields[0].parse::<f32>().is_err()) {
+            // Blank lines and the optional header
+            continue;
+        }
+        let numbers: Vec<f32> = fields.iter().map_while(|f| f.parse().ok()).collect();
+        let start = *numbers
+            .first()
+            .ok_or_else(|| ParseAnnotationError(format!("line {}: invalid time", n + 1)))?;
+        labels.push(Label {
+            start,
+            end: numbers.get(2).filter(|d| **d > 0.0).map(|d| start + d),
+            text: fields[numbers.len()..].join(","),
+            pitch: numbers.get(1).filter(|_| numbers.len() >= 4).copied(),
+        });
+    }
+    Ok(labels)
+}
+
+/// Notes of labels with a pitch, which they keep, or named after a note, such as "C#4"
+/// or "Bb2". `None` if a label has no end, or neither a positive pitch nor a note name.
+pub fn labels_to_notes(labels: &[Label]) -> Option<Vec<NoteEvent>> {
+    labels
+        .iter()
+        .map(|label| {
+            let note = match label.pitch {
+                Some(freq) if freq > 0.0 => Note {
+                    freq,
+                    ..Tuning::default().note(freq)
+                },
+                Some(_) => return None,
+                None => label.text.parse().ok()?,
+            };
+            Some(NoteEvent::new(label.start, label.end?, note))
+        })
+        .collect()
+}
+
+/// Invalid annotation file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseAnnotationError(pub String);
+
+impl fmt::Display for ParseAnnotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid annotation: {}", self.0)
+    }
+}
+
+impl Error for ParseAnnotationError {}
+
+/// Numbered non-empty lines that are not comments, split into fields
+fn data_lines(text: &str) -> impl Iterator<Item = (usize, Vec<&str>)> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(n, line)| (n, split_fields(line)))
+}
+
+/// Fields separated by whitespace or commas
+fn split_fields(line: &str) -> Vec<&str> {
+    line.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|f| !f.is_empty())
+        .collect()
+}
+
+fn parse_time(line: usize, field: &str) -> Result<f32, ParseAnnotationError> {
+    field
+        .parse()
+        .ok()
+        .filter(|t: &f32| t.is_finite() && *t >= 0.0)
+        .ok_or_else(|| ParseAnnotationError(format!("line {}: invalid time {:?}", line, field)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_are_detected_from_their_layout() {
+        let cases = [
+            ("0.5\n1.0\n", AnnotationFormat::Onsets),
+            ("0.50\t0.90\t440.0\n", AnnotationFormat::Mirex),
+            ("0.500000\t0.900000\t440.000000\n", AnnotationFormat::Mirex),
+            ("0.5 0.9 440\n", AnnotationFormat::Mirex),
+            ("0.500000\t0.900000\t1\n", AnnotationFormat::Audacity),
+            ("0.5\t0.9\tA4, slurred\n", AnnotationFormat::Audacity),
+            (
+                "0.500000,440,0.400000,0.8,\n",
+                AnnotationFormat::SonicVisualiser,
+            ),
+            ("0.500000,1,0.400000,\n", AnnotationFormat::SonicVisualiser),
+            ("0.500000,onset\n", AnnotationFormat::SonicVisualiser),
+        ];
+        for (text, format) in cases {
+            assert_eq!(AnnotationFormat::detect(text), format, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn sonic_visualiser_notes_keep_their_value_as_pitch() {
+        let text = "0.500000,261.63,0.400000,0.8,\n1.000000,440,0.250000,0.8,\n";
+        let labels = parse_sonic_visualiser(text).unwrap();
+        let Annotation::Notes(notes) = Annotation::from_labels(labels) else {
+            panic!("expected notes");
+        };
+        let names: Vec<String> = notes.iter().map(|n| n.note.to_string()).collect();
+        assert_eq!(names, ["C4", "A4"]);
+        assert_eq!((notes[1].note.freq, notes[1].offset), (440.0, 1.25));
+
+        // The value of a region is not a pitch
+        let regions = parse_sonic_visualiser("0.5,261.63,0.4,\n").unwrap();
+        assert_eq!(
+            Annotation::from_labels(regions),
+            Annotation::Onsets(vec![0.5])
+        );
+    }
+}
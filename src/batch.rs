@@ -0,0 +1,217 @@
+use std::{
+    error::Error,
+    fmt, fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Extensions of the audio files picked up by a batch, compared case-insensitively
+pub const SUPPORTED_EXTENSIONS: [&str; 2] = ["wav", "wave"];
+
+/// Where the output of every input file is written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputLocation {
+    /// In the directory of the input file
+    NextToInput,
+    /// Under this directory, mirroring the layout of the input directory
+    Mirror(PathBuf),
+}
+
+/// Runs a job on every audio file of a directory tree, on a pool of worker threads.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    /// Number of worker threads
+    pub workers: usize,
+    pub location: OutputLocation,
+    /// Extension of the output files, replacing the one of the input
+    pub extension: String,
+    /// Descend into subdirectories
+    pub recursive: bool,
+}
+
+/// Outcome of a single file
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub elapsed: Duration,
+    /// `None` on success
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchReport {
+    /// One report per input file, in path order
+    pub files: Vec<FileReport>,
+    /// Wall-clock time of the whole batch
+    pub elapsed: Duration,
+}
+
+impl Default for Batch {
+    fn default() -> Self {
+        Batch {
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            location: OutputLocation::NextToInput,
+            extension: "txt".to_string(),
+            recursive: true,
+        }
+    }
+}
+
+impl Batch {
+    pub fn new(location: OutputLocation, extension: &str) -> Self {
+        Batch {
+            location,
+            extension: extension.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Supported audio files under `dir`, sorted by path
+    pub fn inputs(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut inputs = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    if self.recursive {
+                        pending.push(path);
+                    }
+                } else if is_supported(&path) {
+                    inputs.push(path);
+                }
+            }
+        }
+        inputs.sort();
+        Ok(inputs)
+    }
+
+    /// Output file of `input`, found under the input directory `root`
+    pub fn output_path(&self, root: &Path, input: &Path) -> PathBuf {
+        let path = match &self.location {
+            OutputLocation::NextToInput => input.to_path_buf(),
+            OutputLocation::Mirror(out) => out.join(input.strip_prefix(root).unwrap_or(input)),
+        };
+        path.with_extension(&self.extension)
+    }
+
+    /// Runs `job` on every input file under `dir` and writes the returned text to its
+    /// output file. Failures, including panics, are recorded in the report instead of
+    /// stopping the batch.
+    pub fn run<F>(&self, dir: &Path, job: F) -> io::Result<BatchReport>
+    where
+        F: Fn(&Path) -> Result<String, Box<dyn Error>> + Sync,
+    {
+        let start = Instant::now();
+        let inputs = self.inputs(dir)?;
+        let next = AtomicUsize::new(0);
+        let reports = Mutex::new(Vec::with_capacity(inputs.len()));
+
+        thread::scope(|scope| {
+            for _ in 0..self.workers.clamp(1, inputs.len().max(1)) {
+                scope.spawn(|| {
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(input) = inputs.get(i) else { break };
+                        let report = self.process(dir, input, &job);
+                        reports.lock().unwrap().push(report);
+                    }
+                });
+            }
+        });
+
+        let mut files = reports.into_inner().unwrap();
+        files.sort_by(|a, b| a.input.cmp(&b.input));
+        Ok(BatchReport {
+            files,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    fn process<F>(&self, root: &Path, input: &Path, job: &F) -> FileReport
+    where
+        F: Fn(&Path) -> Result<String, Box<dyn Error>>,
+    {
+        let start = Instant::now();
+        let output = self.output_path(root, input);
+
+        let result = match panic::catch_unwind(AssertUnwindSafe(|| job(input))) {
+            Ok(Ok(text)) => write_output(&output, &text).map_err(|e| e.to_string()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(payload) => Err(panic_message(payload.as_ref())),
+        };
+
+        FileReport {
+            input: input.to_path_buf(),
+            output,
+            elapsed: start.elapsed(),
+            error: result.err(),
+        }
+    }
+}
+
+impl BatchReport {
+    pub fn succeeded(&self) -> usize {
+        self.files.iter().filter(|f| f.error.is_none()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.files.len() - self.succeeded()
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for file in self.files.iter() {
+            match &file.error {
+                None => writeln!(
+                    f,
+                    "ok     {} -> {} ({:.2}s)",
+                    file.input.display(),
+                    file.output.display(),
+                    file.elapsed.as_secs_f32()
+                )?,
+                Some(error) => writeln!(f, "failed {}: {}", file.input.display(), error)?,
+            }
+        }
+        write!(
+            f,
+            "{} files in {:.2}s: {} succeeded, {} failed",
+            self.files.len(),
+            self.elapsed.as_secs_f32(),
+            self.succeeded(),
+            self.failed()
+        )
+    }
+}
+
+fn is_supported(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+        SUPPORTED_EXTENSIONS
+            .iter()
+            .any(|s| s.eq_ignore_ascii_case(e))
+    })
+}
+
+fn write_output(path: &Path, text: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, text)
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+    format!("panicked: {}", message)
+}
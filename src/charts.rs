@@ -35,8 +35,7 @@ pub fn plot(data: &[f32], name: &str) -> Result<(), Box<dyn std::error::Error>>
     chart.draw_series(data.iter().enumerate().map(|(i, &value)| {
         let x = i as f32;
         let y = value;
-        let bar = Rectangle::new([(x, 0.0), (x + 0.8, y)], BLUE.filled());
-        bar
+        Rectangle::new([(x, 0.0), (x + 0.8, y)], BLUE.filled())
     }))?;
     Ok(())
 }
@@ -58,8 +57,7 @@ pub fn print_chart(data: &[f32]) -> Result<(), Box<dyn std::error::Error>> {
 
     chart.draw_series(data.iter().enumerate().map(|(i, &value)| {
         let x = i as f32;
-        let bar = Rectangle::new([(x, 0.0), (x + 0.8, value)], BLUE.filled());
-        bar
+        Rectangle::new([(x, 0.0), (x + 0.8, value)], BLUE.filled())
     }))?;
     Ok(())
 }
@@ -88,8 +86,7 @@ pub fn print_frequencies(data: &[(f32, bool)]) -> Result<(), Box<dyn std::error:
     chart.draw_series(data.iter().enumerate().map(|(i, &value)| {
         let x = i as f32;
         let y = if value.1 { value.0 } else { 0.0 };
-        let bar = Rectangle::new([(x, 0.0), (x + 0.8, y)], BLUE.filled());
-        bar
+        Rectangle::new([(x, 0.0), (x + 0.8, y)], BLUE.filled())
     }))?;
     Ok(())
 }
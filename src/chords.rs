@@ -0,0 +1,101 @@
+use std::fmt;
+
+use crate::{key::Key, notes::NoteName};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Dominant7,
+    Major7,
+    Minor7,
+    Sus2,
+    Sus4,
+}
+
+impl ChordQuality {
+    pub const ALL: [ChordQuality; 9] = [
+        ChordQuality::Major,
+        ChordQuality::Minor,
+        ChordQuality::Diminished,
+        ChordQuality::Augmented,
+        ChordQuality::Dominant7,
+        ChordQuality::Major7,
+        ChordQuality::Minor7,
+        ChordQuality::Sus2,
+        ChordQuality::Sus4,
+    ];
+
+    /// Semitones above the root of every chord tone
+    pub fn intervals(&self) -> &'static [u8] {
+        match self {
+            ChordQuality::Major => &[0, 4, 7],
+            ChordQuality::Minor => &[0, 3, 7],
+            ChordQuality::Diminished => &[0, 3, 6],
+            ChordQuality::Augmented => &[0, 4, 8],
+            ChordQuality::Dominant7 => &[0, 4, 7, 10],
+            ChordQuality::Major7 => &[0, 4, 7, 11],
+            ChordQuality::Minor7 => &[0, 3, 7, 10],
+            ChordQuality::Sus2 => &[0, 2, 7],
+            ChordQuality::Sus4 => &[0, 5, 7],
+        }
+    }
+
+    /// Suffix appended to the root in lead sheets
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            ChordQuality::Major => "",
+            ChordQuality::Minor => "m",
+            ChordQuality::Diminished => "dim",
+            ChordQuality::Augmented => "aug",
+            ChordQuality::Dominant7 => "7",
+            ChordQuality::Major7 => "maj7",
+            ChordQuality::Minor7 => "m7",
+            ChordQuality::Sus2 => "sus2",
+            ChordQuality::Sus4 => "sus4",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Chord {
+    pub root: NoteName,
+    pub quality: ChordQuality,
+}
+
+impl Chord {
+    pub fn new(root: NoteName, quality: ChordQuality) -> Self {
+        Chord { root, quality }
+    }
+
+    /// Pitch classes of the chord tones, starting at the root
+    pub fn names(&self) -> Vec<NoteName> {
+        self.quality
+            .intervals()
+            .iter()
+            .map(|i| NoteName::from_pitch_class(self.root.pitch_class() + i))
+            .collect()
+    }
+
+    /// Binary chroma template of the chord, indexed from C
+    pub fn template(&self) -> [f32; 12] {
+        let mut template = [0.0; 12];
+        for name in self.names() {
+            template[name.pitch_class() as usize] = 1.0;
+        }
+        template
+    }
+
+    /// Chord symbol with the root spelled in `key`, e.g. "Bbmaj7"
+    pub fn spell(&self, key: &Key) -> String {
+        format!("{}{}", key.spell(self.root), self.quality.symbol())
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.root, self.quality.symbol())
+    }
+}
@@ -0,0 +1,421 @@
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use transcriber::{
+    annotations::AnnotationFormat,
+    config::{Config, DetectorKind, OdfKind, Preset},
+    scales::NamedScale,
+    temperament::TemperamentSetting,
+};
+
+/// Monophonic music transcription: onsets, tempo, pitch and notes from a WAV file
+#[derive(Debug, Parser)]
+#[command(name = "transcriber", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// Output format. csv and json rows follow the versioned schema of `transcriber::export`
+    #[arg(long, short, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Write the results to this file instead of stdout
+    #[arg(long, short, global = true)]
+    pub output: Option<PathBuf>,
+
+    /// TOML configuration file; flags override its values
+    #[arg(long, short, global = true, conflicts_with = "preset")]
+    pub config: Option<PathBuf>,
+
+    /// Built-in configuration: voice, guitar, bass, flute or violin
+    #[arg(long, short, global = true)]
+    pub preset: Option<Preset>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Detect note onsets
+    Onsets {
+        /// Input WAV file
+        input: PathBuf,
+        #[command(flatten)]
+        onsets: OnsetArgs,
+    },
+    /// Compute the onset detection function, standardized, frame by frame
+    Odf {
+        /// Input WAV file
+        input: PathBuf,
+        #[command(flatten)]
+        onsets: OnsetArgs,
+    },
+    /// Estimate the tempo from the onset detection function
+    Tempo {
+        /// Input WAV file
+        input: PathBuf,
+        #[command(flatten)]
+        onsets: OnsetArgs,
+    },
+    /// Track the fundamental frequency frame by frame
+    Pitch {
+        /// Input WAV file
+        input: PathBuf,
+        #[command(flatten)]
+        pitch: PitchArgs,
+    },
+    /// Segment the recording into notes
+    Transcribe {
+        /// Input WAV file
+        input: PathBuf,
+        #[command(flatten)]
+        onsets: OnsetArgs,
+        #[command(flatten)]
+        pitch: PitchArgs,
+        #[command(flatten)]
+        notes: NoteArgs,
+    },
+    /// Transcribe a WAV file and score it against a reference annotation
+    Evaluate {
+        /// Input WAV file
+        input: PathBuf,
+        /// Annotation: MIDI file, onset list, MIREX note list, Sonic Visualiser CSV or
+        /// Audacity labels
+        #[arg(long, short)]
+        reference: PathBuf,
+        /// Format of the reference: midi, onsets, mirex, sonic-visualiser or audacity
+        /// [default: from the extension and content]
+        #[arg(long)]
+        reference_format: Option<AnnotationFormat>,
+        /// Maximum onset deviation in seconds [default: 0.05]
+        #[arg(long)]
+        onset_tolerance: Option<f32>,
+        #[command(flatten)]
+        onsets: OnsetArgs,
+        #[command(flatten)]
+        pitch: PitchArgs,
+        #[command(flatten)]
+        notes: NoteArgs,
+    },
+    /// Render the transcription to a WAV file, to check it by ear
+    Sonify {
+        /// Input WAV file
+        input: PathBuf,
+        /// Output WAV file
+        wav: PathBuf,
+        /// Render the frame-wise pitch track instead of the notes
+        #[arg(long)]
+        pitch_track: bool,
+        /// Level of the original recording in the mix, from 0 (resynthesis only) to 1
+        /// [default: 0]
+        #[arg(long)]
+        balance: Option<f32>,
+        /// Add clicks at the detected onsets
+        #[arg(long)]
+        clicks: bool,
+        #[command(flatten)]
+        onsets: OnsetArgs,
+        #[command(flatten)]
+        pitch: PitchArgs,
+        #[command(flatten)]
+        notes: NoteArgs,
+    },
+    /// Transcribe every WAV file of a directory tree in parallel
+    Batch {
+        /// Input directory
+        input: PathBuf,
+        /// Write the outputs under this directory, mirroring the input tree, instead of
+        /// next to each input file
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+        /// Number of worker threads [default: number of CPUs]
+        #[arg(long, short)]
+        jobs: Option<usize>,
+        /// Don't descend into subdirectories
+        #[arg(long)]
+        no_recursive: bool,
+        #[command(flatten)]
+        onsets: OnsetArgs,
+        #[command(flatten)]
+        pitch: PitchArgs,
+        #[command(flatten)]
+        notes: NoteArgs,
+    },
+    /// Search the onset detection and peak-picking parameters that maximize the onset
+    /// F-measure over a directory of annotated WAV files, and save them to a config file
+    Tune {
+        /// Directory of WAV files, each with an annotation of the same name: .mid, .midi,
+        /// .txt, .csv, .lab or .onsets
+        input: PathBuf,
+        /// Config file to write, starting from --config or --preset
+        #[arg(long, short)]
+        save: PathBuf,
+        /// Evaluate this many random combinations instead of the whole grid
+        #[arg(long)]
+        random: Option<usize>,
+        /// Seed of the random search [default: 0]
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Format of the annotations [default: from the extension and content]
+        #[arg(long)]
+        reference_format: Option<AnnotationFormat>,
+        /// Maximum onset deviation in seconds [default: 0.05]
+        #[arg(long)]
+        onset_tolerance: Option<f32>,
+        /// Onset detection function [default: rcd]
+        #[arg(long, value_enum)]
+        odf: Option<Odf>,
+    },
+    /// Draw the waveform, ODF, onsets and pitch track as PNG charts under charts/
+    Plot {
+        /// Input WAV file
+        input: PathBuf,
+        #[command(flatten)]
+        onsets: OnsetArgs,
+        #[command(flatten)]
+        pitch: PitchArgs,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+impl OutputFormat {
+    /// Extension of the files written in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Text => "txt",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Odf {
+    /// Rectified complex domain
+    Rcd,
+    ComplexDomain,
+    SpectralFlux,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Detector {
+    Pyin,
+    Yin,
+    /// Harmonic product spectrum
+    Hps,
+    /// Harmonic sum spectrum
+    Hss,
+    Cepstrum,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct OnsetArgs {
+    /// Onset detection function [default: rcd]
+    #[arg(long, value_enum)]
+    pub odf: Option<Odf>,
+    /// STFT frame size of the onset detection function, in samples [default: 2048]
+    #[arg(long)]
+    pub onset_frame: Option<usize>,
+    /// STFT hop size of the onset detection function, in samples [default: 441]
+    #[arg(long)]
+    pub onset_hop: Option<usize>,
+    /// Half width in frames of the peak-picking window [default: 3]
+    #[arg(long)]
+    pub window: Option<usize>,
+    /// Multiplier of the window for the local mean [default: 3]
+    #[arg(long)]
+    pub multiplier: Option<usize>,
+    /// Minimum height of a peak over the local mean [default: 0.5]
+    #[arg(long)]
+    pub delta: Option<f64>,
+    /// Decay of the adaptive threshold [default: 0.4]
+    #[arg(long)]
+    pub alpha: Option<f64>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct PitchArgs {
+    /// f0 estimator [default: pyin]
+    #[arg(long, value_enum)]
+    pub detector: Option<Detector>,
+    /// Minimum frequency in Hz [default: 40]
+    #[arg(long)]
+    pub fmin: Option<f64>,
+    /// Maximum frequency in Hz [default: 600]
+    #[arg(long)]
+    pub fmax: Option<f64>,
+    /// Analysis frame size of the f0 estimator, in samples [default: 4096]
+    #[arg(long)]
+    pub pitch_frame: Option<usize>,
+    /// Hop size of the f0 estimator, in samples [default: a quarter of the frame]
+    #[arg(long)]
+    pub pitch_hop: Option<usize>,
+    /// Frames with a lower voicing probability are treated as unvoiced [default: 0.7]
+    #[arg(long)]
+    pub min_voiced_prob: Option<f64>,
+    /// Reference frequency of A4 in Hz [default: estimated from the recording]
+    #[arg(long)]
+    pub a4: Option<f64>,
+    /// Restrict notes to a scale, e.g. "D dorian" or "A minor-pentatonic"
+    #[arg(long)]
+    pub scale: Option<NamedScale>,
+    /// Cost in cents of a note outside --scale [default: notes always snap to the scale]
+    #[arg(long)]
+    pub scale_penalty: Option<f64>,
+    /// 12-tet, just, pythagorean, meantone or a 12-note Scala (.scl) file [default: 12-tet]
+    #[arg(long)]
+    pub temperament: Option<TemperamentSetting>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct NoteArgs {
+    /// Notes shorter than this, in seconds, are dropped [default: 0.05]
+    #[arg(long)]
+    pub min_duration: Option<f64>,
+    /// Standardized ODF value at which a repeated note is as likely as not [default: 2]
+    #[arg(long)]
+    pub onset_threshold: Option<f64>,
+    /// Don't split notes on legato pitch changes and energy dips
+    #[arg(long)]
+    pub no_split: bool,
+    /// Energy dips below this fraction of the surrounding peaks split a note [default: 0.5]
+    #[arg(long)]
+    pub dip_ratio: Option<f64>,
+}
+
+impl Cli {
+    /// Configuration from the config file or preset, with the flags of the command
+    /// applied, loaded and validated. Paths given as flags are relative to the
+    /// working directory.
+    pub fn config(&self) -> Result<Config, Box<dyn std::error::Error>> {
+        let mut config = match (&self.config, self.preset) {
+            (Some(path), _) => Config::from_file(path)?,
+            (None, Some(preset)) => preset.config(),
+            (None, None) => Config::default(),
+        };
+        self.command.apply(&mut config);
+        config.load(Path::new(""))?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl Command {
+    /// Overrides the values of `config` given by the flags of the command
+    pub fn apply(&self, config: &mut Config) {
+        match self {
+            Command::Onsets { onsets, .. }
+            | Command::Odf { onsets, .. }
+            | Command::Tempo { onsets, .. } => onsets.apply(config),
+            Command::Pitch { pitch, .. } => pitch.apply(config),
+            Command::Transcribe {
+                onsets,
+                pitch,
+                notes,
+                ..
+            }
+            | Command::Evaluate {
+                onsets,
+                pitch,
+                notes,
+                ..
+            }
+            | Command::Sonify {
+                onsets,
+                pitch,
+                notes,
+                ..
+            }
+            | Command::Batch {
+                onsets,
+                pitch,
+                notes,
+                ..
+            } => {
+                onsets.apply(config);
+                pitch.apply(config);
+                notes.apply(config);
+            }
+            Command::Tune { odf, .. } => {
+                override_with(&mut config.onsets.odf, odf.map(OdfKind::from));
+            }
+            Command::Plot { onsets, pitch, .. } => {
+                onsets.apply(config);
+                pitch.apply(config);
+            }
+        }
+    }
+}
+
+impl OnsetArgs {
+    pub fn apply(&self, config: &mut Config) {
+        let onsets = &mut config.onsets;
+        override_with(&mut onsets.odf, self.odf.map(OdfKind::from));
+        override_with(&mut onsets.frame_size, self.onset_frame);
+        override_with(&mut onsets.hop_size, self.onset_hop);
+        override_with(&mut onsets.window, self.window);
+        override_with(&mut onsets.multiplier, self.multiplier);
+        override_with(&mut onsets.delta, self.delta);
+        override_with(&mut onsets.alpha, self.alpha);
+    }
+}
+
+impl PitchArgs {
+    pub fn apply(&self, config: &mut Config) {
+        let pitch = &mut config.pitch;
+        override_with(&mut pitch.detector, self.detector.map(DetectorKind::from));
+        override_with(&mut pitch.fmin, self.fmin);
+        override_with(&mut pitch.fmax, self.fmax);
+        override_with(&mut pitch.frame_size, self.pitch_frame);
+        override_with(&mut pitch.hop_size, self.pitch_hop.map(Some));
+        override_with(&mut pitch.min_voiced_prob, self.min_voiced_prob);
+        override_with(&mut pitch.a4, self.a4.map(Some));
+        override_with(&mut pitch.scale, self.scale.map(Some));
+        override_with(&mut pitch.scale_penalty, self.scale_penalty.map(Some));
+        override_with(&mut pitch.temperament, self.temperament.clone().map(Some));
+    }
+}
+
+impl NoteArgs {
+    pub fn apply(&self, config: &mut Config) {
+        let notes = &mut config.notes;
+        override_with(&mut notes.min_duration, self.min_duration);
+        override_with(&mut notes.onset_threshold, self.onset_threshold);
+        if self.no_split {
+            notes.split = false;
+        }
+        override_with(&mut notes.dip_ratio, self.dip_ratio);
+    }
+}
+
+fn override_with<T>(value: &mut T, flag: Option<T>) {
+    if let Some(flag) = flag {
+        *value = flag;
+    }
+}
+
+impl From<Odf> for OdfKind {
+    fn from(odf: Odf) -> Self {
+        match odf {
+            Odf::Rcd => OdfKind::Rcd,
+            Odf::ComplexDomain => OdfKind::ComplexDomain,
+            Odf::SpectralFlux => OdfKind::SpectralFlux,
+        }
+    }
+}
+
+impl From<Detector> for DetectorKind {
+    fn from(detector: Detector) -> Self {
+        match detector {
+            Detector::Pyin => DetectorKind::Pyin,
+            Detector::Yin => DetectorKind::Yin,
+            Detector::Hps => DetectorKind::Hps,
+            Detector::Hss => DetectorKind::Hss,
+            Detector::Cepstrum => DetectorKind::Cepstrum,
+        }
+    }
+}
@@ -0,0 +1,546 @@
+use std::{error::Error, fmt, fs, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    algorithms::{
+        note_splitting::NoteSplitter,
+        note_tracking::NoteTracker,
+        onset_detection::StftBasedOnset,
+        peak_picking::peak_picking,
+        pitch::{PitchDetector, PitchTrack},
+        shared::OdfTiming,
+        spectral_pitch::{SpectralMethod, SpectralPitch},
+        yin::{PYin, Yin},
+    },
+    scales::{NamedScale, ScaleConstraint},
+    temperament::{ParseTemperamentError, TemperamentSetting},
+    tuning::Tuning,
+};
+
+/// Parameters of the whole transcription pipeline, as stored in a TOML file. Missing
+/// keys take their default value.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub onsets: OnsetConfig,
+    pub pitch: PitchConfig,
+    pub notes: NoteConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OdfKind {
+    /// Rectified complex domain
+    Rcd,
+    ComplexDomain,
+    SpectralFlux,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DetectorKind {
+    Pyin,
+    Yin,
+    /// Harmonic product spectrum
+    Hps,
+    /// Harmonic sum spectrum
+    Hss,
+    Cepstrum,
+}
+
+/// Onset detection function and peak picking
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OnsetConfig {
+    pub odf: OdfKind,
+    /// STFT frame size in samples
+    pub frame_size: usize,
+    /// STFT hop size in samples
+    pub hop_size: usize,
+    /// Half width in frames of the peak-picking window
+    pub window: usize,
+    /// Multiplier of the window for the local mean
+    pub multiplier: usize,
+    /// Minimum height of a peak over the local mean
+    pub delta: f64,
+    /// Decay of the adaptive threshold
+    pub alpha: f64,
+}
+
+impl Default for OnsetConfig {
+    fn default() -> Self {
+        OnsetConfig {
+            odf: OdfKind::Rcd,
+            frame_size: 2048,
+            hop_size: 441,
+            window: 3,
+            multiplier: 3,
+            delta: 0.5,
+            alpha: 0.4,
+        }
+    }
+}
+
+/// f0 estimation and tuning
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PitchConfig {
+    pub detector: DetectorKind,
+    /// Minimum frequency in Hz
+    pub fmin: f64,
+    /// Maximum frequency in Hz
+    pub fmax: f64,
+    /// Analysis frame size in samples
+    pub frame_size: usize,
+    /// Hop size in samples, a quarter of the frame if missing
+    pub hop_size: Option<usize>,
+    /// Frames with a lower voicing probability are treated as unvoiced
+    pub min_voiced_prob: f64,
+    /// Reference frequency of A4 in Hz, estimated from the recording if missing
+    pub a4: Option<f64>,
+    /// Restricts notes to a scale, e.g. "D dorian"
+    pub scale: Option<NamedScale>,
+    /// Cost in cents of a note outside `scale`; every note snaps to the scale if
+    /// missing
+    pub scale_penalty: Option<f64>,
+    /// "12-tet" (the default), "just", "pythagorean", "meantone" or a 12-note Scala
+    /// file, relative to the config file
+    pub temperament: Option<TemperamentSetting>,
+}
+
+impl Default for PitchConfig {
+    fn default() -> Self {
+        PitchConfig {
+            detector: DetectorKind::Pyin,
+            fmin: 40.0,
+            fmax: 600.0,
+            frame_size: 4096,
+            hop_size: None,
+            min_voiced_prob: 0.7,
+            a4: None,
+            scale: None,
+            scale_penalty: None,
+            temperament: None,
+        }
+    }
+}
+
+/// Note segmentation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoteConfig {
+    /// Notes shorter than this, in seconds, are dropped
+    pub min_duration: f64,
+    /// Standardized ODF value at which a repeated note is as likely as not
+    pub onset_threshold: f64,
+    /// Split notes on legato pitch changes and energy dips
+    pub split: bool,
+    /// Energy dips below this fraction of the surrounding peaks split a note
+    pub dip_ratio: f64,
+}
+
+impl Default for NoteConfig {
+    fn default() -> Self {
+        NoteConfig {
+            min_duration: 0.05,
+            onset_threshold: 2.0,
+            split: true,
+            dip_ratio: 0.5,
+        }
+    }
+}
+
+impl Config {
+    /// Loads a TOML config file, and the files it refers to relative to its directory
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut config = Config::parse(&fs::read_to_string(path)?)?;
+        config.load(path.parent().unwrap_or(Path::new("")))?;
+        Ok(config)
+    }
+
+    /// Reads the files the config refers to, a Scala temperament, resolving relative
+    /// paths against `dir`
+    pub fn load(&mut self, dir: &Path) -> Result<(), ParseTemperamentError> {
+        match &mut self.pitch.temperament {
+            Some(temperament) => temperament.load(dir),
+            None => Ok(()),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("config is always representable in TOML")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_toml())?;
+        Ok(())
+    }
+
+    /// Checks that every value is usable by the analyses, which could otherwise panic
+    /// or return nonsense
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let onsets = &self.onsets;
+        check(
+            onsets.frame_size >= 2,
+            "onsets.frame_size must be at least 2",
+        )?;
+        check(onsets.hop_size > 0, "onsets.hop_size must be positive")?;
+        check(onsets.window > 0, "onsets.window must be positive")?;
+        check(onsets.delta.is_finite(), "onsets.delta must be finite")?;
+        check(
+            (0.0..=1.0).contains(&onsets.alpha),
+            "onsets.alpha must be in [0, 1]",
+        )?;
+
+        let pitch = &self.pitch;
+        check(pitch.frame_size >= 4, "pitch.frame_size must be at least 4")?;
+        check(pitch.hop_size() > 0, "pitch.hop_size must be positive")?;
+        check(
+            pitch.fmin > 0.0 && pitch.fmin < pitch.fmax,
+            "pitch.fmin must be positive and below pitch.fmax",
+        )?;
+        check(
+            (0.0..=1.0).contains(&pitch.min_voiced_prob),
+            "pitch.min_voiced_prob must be in [0, 1]",
+        )?;
+        check(
+            pitch.a4.is_none_or(|a4| a4 > 0.0),
+            "pitch.a4 must be positive",
+        )?;
+        check(
+            pitch.scale_penalty.is_none_or(|p| p >= 0.0),
+            "pitch.scale_penalty must not be negative",
+        )?;
+        check(
+            pitch
+                .temperament
+                .as_ref()
+                .is_none_or(|t| t.offsets().is_some()),
+            "pitch.temperament must be loaded with Config::load",
+        )?;
+
+        let notes = &self.notes;
+        check(
+            notes.min_duration >= 0.0,
+            "notes.min_duration must not be negative",
+        )?;
+        check(
+            notes.onset_threshold.is_finite(),
+            "notes.onset_threshold must be finite",
+        )?;
+        check(
+            (0.0..=1.0).contains(&notes.dip_ratio),
+            "notes.dip_ratio must be in [0, 1]",
+        )
+    }
+
+    pub fn note_tracker(&self) -> NoteTracker {
+        NoteTracker {
+            min_duration: self.notes.min_duration as f32,
+            onset_threshold: self.notes.onset_threshold as f32,
+            ..NoteTracker::new(self.pitch.fmin as f32, self.pitch.fmax as f32)
+        }
+    }
+
+    pub fn note_splitter(&self) -> NoteSplitter {
+        NoteSplitter {
+            min_voiced_prob: self.pitch.min_voiced_prob as f32,
+            dip_ratio: self.notes.dip_ratio as f32,
+            ..NoteSplitter::default()
+        }
+    }
+}
+
+impl OdfKind {
+    /// STFT frames consumed before the first value: the complex domain functions
+    /// predict a frame from the two previous ones, the flux compares it with one
+    pub fn lag(&self) -> usize {
+        match self {
+            OdfKind::Rcd | OdfKind::ComplexDomain => 2,
+            OdfKind::SpectralFlux => 1,
+        }
+    }
+}
+
+impl OnsetConfig {
+    /// Frames per second of the onset detection function
+    pub fn odf_rate(&self, sample_rate: u32) -> f32 {
+        self.timing(sample_rate).rate()
+    }
+
+    /// Time base of the onset detection function of a recording at `sample_rate`
+    pub fn timing(&self, sample_rate: u32) -> OdfTiming {
+        OdfTiming {
+            lag: self.odf.lag(),
+            hop_size: self.hop_size,
+            frame_size: self.frame_size,
+            sample_rate,
+        }
+    }
+
+    /// Raw onset detection function of `samples`
+    pub fn detection_function(&self, samples: &[f32]) -> Vec<f32> {
+        let onset = StftBasedOnset::new(samples, self.frame_size, self.hop_size);
+        match self.odf {
+            OdfKind::Rcd => onset.rcd(),
+            OdfKind::ComplexDomain => onset.complex_domain(),
+            OdfKind::SpectralFlux => onset.spectral_flux(),
+        }
+    }
+
+    /// Onset frames of `odf`, which is standardized in place
+    pub fn peaks(&self, odf: &mut [f32]) -> Vec<bool> {
+        peak_picking(
+            odf,
+            self.window,
+            self.multiplier,
+            self.delta as f32,
+            self.alpha as f32,
+        )
+    }
+}
+
+impl PitchConfig {
+    pub fn hop_size(&self) -> usize {
+        self.hop_size.unwrap_or(self.frame_size / 4)
+    }
+
+    pub fn detector(&self) -> Box<dyn PitchDetector> {
+        let hop = self.hop_size();
+        let spectral = |method| SpectralPitch {
+            fmin: self.fmin as f32,
+            fmax: self.fmax as f32,
+            ..SpectralPitch::new(method, self.frame_size, hop)
+        };
+        match self.detector {
+            DetectorKind::Pyin => Box::new(PYin {
+                hop_length: hop,
+                ..PYin::new(self.fmin, self.fmax, self.frame_size)
+            }),
+            DetectorKind::Yin => Box::new(Yin::new(self.frame_size, hop)),
+            DetectorKind::Hps => Box::new(spectral(SpectralMethod::HarmonicProduct)),
+            DetectorKind::Hss => Box::new(spectral(SpectralMethod::HarmonicSum)),
+            DetectorKind::Cepstrum => Box::new(spectral(SpectralMethod::Cepstrum)),
+        }
+    }
+
+    /// The configured tuning, or the one estimated from `track`, in the configured
+    /// temperament and with the scale constraint if there is one
+    pub fn tuning(&self, track: &PitchTrack) -> Tuning {
+        let mut tuning = Tuning::default();
+        if let Some(offsets) = self.temperament.as_ref().and_then(|t| t.offsets()) {
+            tuning.offsets = offsets;
+        }
+        let tuning = match self.a4 {
+            Some(a4) => Tuning {
+                a4: a4 as f32,
+                ..tuning
+            },
+            None => tuning.estimated(track, self.min_voiced_prob as f32),
+        };
+        match self.scale_constraint() {
+            Some(constraint) => tuning.with_scale(constraint),
+            None => tuning,
+        }
+    }
+
+    pub fn scale_constraint(&self) -> Option<ScaleConstraint> {
+        let scale = self.scale?.into();
+        Some(match self.scale_penalty {
+            Some(penalty) => ScaleConstraint::new(scale, penalty as f32),
+            None => ScaleConstraint::strict(scale),
+        })
+    }
+}
+
+/// Built-in configurations tuned to the range and articulation of an instrument
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Preset {
+    Voice,
+    Guitar,
+    Bass,
+    Flute,
+    Violin,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 5] = [
+        Preset::Voice,
+        Preset::Guitar,
+        Preset::Bass,
+        Preset::Flute,
+        Preset::Violin,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Voice => "voice",
+            Preset::Guitar => "guitar",
+            Preset::Bass => "bass",
+            Preset::Flute => "flute",
+            Preset::Violin => "violin",
+        }
+    }
+
+    pub fn config(&self) -> Config {
+        let default = Config::default();
+        match self {
+            // E2 to C6, soft onsets and frequent slurs
+            Preset::Voice => Config {
+                onsets: OnsetConfig {
+                    delta: 0.3,
+                    ..default.onsets
+                },
+                pitch: PitchConfig {
+                    fmin: 75.0,
+                    fmax: 1100.0,
+                    frame_size: 2048,
+                    min_voiced_prob: 0.6,
+                    ..default.pitch
+                },
+                notes: NoteConfig {
+                    min_duration: 0.08,
+                    onset_threshold: 2.5,
+                    ..default.notes
+                },
+            },
+            // E2 to E6, plucked
+            Preset::Guitar => Config {
+                onsets: OnsetConfig {
+                    odf: OdfKind::SpectralFlux,
+                    ..default.onsets
+                },
+                pitch: PitchConfig {
+                    fmin: 75.0,
+                    fmax: 1400.0,
+                    frame_size: 2048,
+                    ..default.pitch
+                },
+                notes: NoteConfig {
+                    onset_threshold: 1.5,
+                    ..default.notes
+                },
+            },
+            // E1 to G4, plucked, long frames for the lowest notes
+            Preset::Bass => Config {
+                onsets: OnsetConfig {
+                    odf: OdfKind::SpectralFlux,
+                    frame_size: 4096,
+                    ..default.onsets
+                },
+                pitch: PitchConfig {
+                    fmin: 38.0,
+                    fmax: 420.0,
+                    frame_size: 4096,
+                    ..default.pitch
+                },
+                notes: NoteConfig {
+                    min_duration: 0.08,
+                    onset_threshold: 1.5,
+                    ..default.notes
+                },
+            },
+            // C4 to C7, breathy and often tongued softly
+            Preset::Flute => Config {
+                onsets: OnsetConfig {
+                    delta: 0.3,
+                    ..default.onsets
+                },
+                pitch: PitchConfig {
+                    fmin: 250.0,
+                    fmax: 2200.0,
+                    frame_size: 1024,
+                    min_voiced_prob: 0.6,
+                    ..default.pitch
+                },
+                notes: NoteConfig {
+                    onset_threshold: 2.5,
+                    ..default.notes
+                },
+            },
+            // G3 to A7, bowed, with slurs and vibrato
+            Preset::Violin => Config {
+                onsets: OnsetConfig {
+                    delta: 0.3,
+                    ..default.onsets
+                },
+                pitch: PitchConfig {
+                    fmin: 190.0,
+                    fmax: 3600.0,
+                    frame_size: 1024,
+                    ..default.pitch
+                },
+                notes: NoteConfig {
+                    onset_threshold: 2.5,
+                    ..default.notes
+                },
+            },
+        }
+    }
+}
+
+impl From<Preset> for Config {
+    fn from(preset: Preset) -> Self {
+        preset.config()
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Preset {
+    type Err = ParsePresetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Preset::ALL
+            .into_iter()
+            .find(|p| p.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| ParsePresetError(s.to_string()))
+    }
+}
+
+fn check(condition: bool, message: &str) -> Result<(), ConfigError> {
+    if condition {
+        Ok(())
+    } else {
+        Err(ConfigError(message.to_string()))
+    }
+}
+
+/// A config value outside of its valid range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError(pub String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid config: {}", self.0)
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Name that matches no built-in preset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePresetError(pub String);
+
+impl fmt::Display for ParsePresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = Preset::ALL.iter().map(|p| p.name()).collect();
+        write!(
+            f,
+            "unknown preset {:?}, expected one of: {}",
+            self.0,
+            names.join(", ")
+        )
+    }
+}
+
+impl Error for ParsePresetError {}
@@ -0,0 +1,325 @@
+use std::fmt;
+
+use crate::{algorithms::pitch::PitchTrack, notes::NoteEvent};
+
+/// Precision, recall and F-measure of a set of matched events
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Scores {
+    pub precision: f32,
+    pub recall: f32,
+    pub f_measure: f32,
+}
+
+impl Scores {
+    /// Scores of `matched` events out of `reference` expected and `estimated` found.
+    /// Every score is 0 when either set is empty.
+    pub fn from_counts(matched: usize, reference: usize, estimated: usize) -> Self {
+        if reference == 0 || estimated == 0 {
+            return Scores::default();
+        }
+        let precision = matched as f32 / estimated as f32;
+        let recall = matched as f32 / reference as f32;
+        Scores {
+            precision,
+            recall,
+            f_measure: f_measure(precision, recall),
+        }
+    }
+}
+
+impl fmt::Display for Scores {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "P = {:.3} R = {:.3} F = {:.3}",
+            self.precision, self.recall, self.f_measure
+        )
+    }
+}
+
+/// Onset scores as in `mir_eval.onset.f_measure`: every reference onset is matched to
+/// at most one estimated onset within `window` seconds (0.05 in mir_eval).
+pub fn onset_scores(reference: &[f32], estimated: &[f32], window: f32) -> Scores {
+    let matches = match_events(reference.len(), estimated.len(), |r, e| {
+        (reference[r] - estimated[e]).abs() <= window
+    });
+    Scores::from_counts(matches.len(), reference.len(), estimated.len())
+}
+
+/// Note-level metrics as in `mir_eval.transcription.precision_recall_f1_overlap`
+#[derive(Debug, Clone)]
+pub struct NoteMetrics {
+    /// Maximum onset deviation in seconds
+    pub onset_tolerance: f32,
+    /// Maximum pitch deviation in cents
+    pub pitch_tolerance: f32,
+    /// Maximum offset deviation as a fraction of the reference duration, offsets are
+    /// ignored if `None`
+    pub offset_ratio: Option<f32>,
+    /// Lower bound of the offset tolerance in seconds
+    pub offset_min_tolerance: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NoteScores {
+    pub scores: Scores,
+    /// Mean ratio between the intersection and the union of matched notes
+    pub average_overlap_ratio: f32,
+}
+
+impl Default for NoteMetrics {
+    fn default() -> Self {
+        NoteMetrics {
+            onset_tolerance: 0.05,
+            pitch_tolerance: 50.0,
+            offset_ratio: Some(0.2),
+            offset_min_tolerance: 0.05,
+        }
+    }
+}
+
+impl NoteMetrics {
+    /// Metrics that only compare onsets and pitches
+    pub fn onset_only() -> Self {
+        NoteMetrics {
+            offset_ratio: None,
+            ..Default::default()
+        }
+    }
+
+    pub fn evaluate(&self, reference: &[NoteEvent], estimated: &[NoteEvent]) -> NoteScores {
+        let matches = match_events(reference.len(), estimated.len(), |r, e| {
+            self.is_match(&reference[r], &estimated[e])
+        });
+
+        let overlaps: Vec<f32> = matches
+            .iter()
+            .map(|&(r, e)| overlap_ratio(&reference[r], &estimated[e]))
+            .collect();
+        let average_overlap_ratio = if overlaps.is_empty() {
+            0.0
+        } else {
+            overlaps.iter().sum::<f32>() / overlaps.len() as f32
+        };
+
+        NoteScores {
+            scores: Scores::from_counts(matches.len(), reference.len(), estimated.len()),
+            average_overlap_ratio,
+        }
+    }
+
+    fn is_match(&self, reference: &NoteEvent, estimated: &NoteEvent) -> bool {
+        let onset = (reference.onset - estimated.onset).abs() <= self.onset_tolerance;
+        let pitch = cents(reference.note.freq, estimated.note.freq).abs() <= self.pitch_tolerance;
+        let offset = self.offset_ratio.is_none_or(|ratio| {
+            let tolerance = (ratio * reference.duration()).max(self.offset_min_tolerance);
+            (reference.offset - estimated.offset).abs() <= tolerance
+        });
+        onset && pitch && offset
+    }
+}
+
+/// Melody metrics as in `mir_eval.melody`, over the frames of the reference track.
+/// Estimated frequencies are interpolated linearly at the reference times, and the
+/// voicing taken from the nearest estimated frame.
+#[derive(Debug, Clone)]
+pub struct MelodyMetrics {
+    /// Maximum pitch deviation in cents
+    pub cent_tolerance: f32,
+    /// Estimated frames with a lower voicing probability count as unvoiced
+    pub min_voiced_prob: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MelodyScores {
+    /// Fraction of voiced reference frames that are estimated voiced
+    pub voicing_recall: f32,
+    /// Fraction of unvoiced reference frames that are estimated voiced
+    pub voicing_false_alarm: f32,
+    /// Fraction of voiced reference frames with a correct pitch, whatever the voicing
+    /// of the estimate
+    pub raw_pitch_accuracy: f32,
+    /// As `raw_pitch_accuracy`, ignoring octave errors
+    pub raw_chroma_accuracy: f32,
+    /// Fraction of frames with a correct voicing and, if voiced, a correct pitch
+    pub overall_accuracy: f32,
+}
+
+impl Default for MelodyMetrics {
+    fn default() -> Self {
+        MelodyMetrics {
+            cent_tolerance: 50.0,
+            min_voiced_prob: 0.5,
+        }
+    }
+}
+
+impl MelodyMetrics {
+    /// Reference frames are voiced when they have an f0
+    pub fn evaluate(&self, reference: &PitchTrack, estimated: &PitchTrack) -> MelodyScores {
+        let (mut voiced, mut unvoiced) = (0, 0);
+        let (mut recalled, mut false_alarms) = (0, 0);
+        let (mut pitch, mut chroma, mut overall) = (0, 0, 0);
+
+        for frame in reference.frames.iter() {
+            let (estimated_f0, estimated_voiced) = self.sample(estimated, frame.time);
+            match frame.f0 {
+                Some(reference_f0) => {
+                    voiced += 1;
+                    let error = estimated_f0.map(|f| cents(reference_f0, f));
+                    let pitch_ok = error.is_some_and(|c| c.abs() <= self.cent_tolerance);
+                    let chroma_ok = error.is_some_and(|c| {
+                        let folded = c.rem_euclid(1200.0);
+                        folded.min(1200.0 - folded) <= self.cent_tolerance
+                    });
+                    recalled += estimated_voiced as usize;
+                    pitch += pitch_ok as usize;
+                    chroma += chroma_ok as usize;
+                    overall += (estimated_voiced && pitch_ok) as usize;
+                }
+                None => {
+                    unvoiced += 1;
+                    false_alarms += estimated_voiced as usize;
+                    overall += !estimated_voiced as usize;
+                }
+            }
+        }
+
+        let ratio = |count: usize, total: usize| {
+            if total == 0 {
+                0.0
+            } else {
+                count as f32 / total as f32
+            }
+        };
+        MelodyScores {
+            voicing_recall: ratio(recalled, voiced),
+            voicing_false_alarm: ratio(false_alarms, unvoiced),
+            raw_pitch_accuracy: ratio(pitch, voiced),
+            raw_chroma_accuracy: ratio(chroma, voiced),
+            overall_accuracy: ratio(overall, voiced + unvoiced),
+        }
+    }
+
+    /// f0 and voicing of `track` at `time`
+    fn sample(&self, track: &PitchTrack, time: f32) -> (Option<f32>, bool) {
+        if track.is_empty() {
+            return (None, false);
+        }
+        let nearest = &track.frames[track.frame_at(time)];
+        let voiced = nearest.f0.is_some() && nearest.voiced_prob >= self.min_voiced_prob;
+
+        let next = track.frames.partition_point(|f| f.time < time);
+        let f0 = match (next.checked_sub(1), track.frames.get(next)) {
+            (Some(previous), Some(next)) => {
+                let previous = &track.frames[previous];
+                match (previous.f0, next.f0) {
+                    (Some(a), Some(b)) => {
+                        let t = (time - previous.time) / (next.time - previous.time);
+                        Some(a + (b - a) * t)
+                    }
+                    _ => nearest.f0,
+                }
+            }
+            _ => nearest.f0,
+        };
+        (f0, voiced)
+    }
+}
+
+/// Tempo scores as in `mir_eval.tempo.detection`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TempoScores {
+    /// Weighted fraction of the two reference tempi that were found
+    pub p_score: f32,
+    pub one_correct: bool,
+    pub both_correct: bool,
+}
+
+/// Compares the estimated tempi, in BPM, with the two reference tempi of an
+/// annotation, where `weight` is the salience of the first one. A reference tempo is
+/// found if an estimate is within `tolerance` (0.08 in mir_eval) of it, relatively.
+pub fn tempo_scores(
+    reference: [f32; 2],
+    weight: f32,
+    estimated: &[f32],
+    tolerance: f32,
+) -> TempoScores {
+    let hits = reference.map(|tempo| {
+        tempo > 0.0
+            && estimated
+                .iter()
+                .any(|estimate| (tempo - estimate).abs() / tempo <= tolerance)
+    });
+    TempoScores {
+        p_score: weight * hits[0] as u8 as f32 + (1.0 - weight) * hits[1] as u8 as f32,
+        one_correct: hits[0] || hits[1],
+        both_correct: hits[0] && hits[1],
+    }
+}
+
+fn f_measure(precision: f32, recall: f32) -> f32 {
+    if precision + recall == 0.0 {
+        0.0
+    } else {
+        2.0 * precision * recall / (precision + recall)
+    }
+}
+
+fn cents(reference: f32, estimated: f32) -> f32 {
+    1200.0 * (estimated / reference).log2()
+}
+
+fn overlap_ratio(a: &NoteEvent, b: &NoteEvent) -> f32 {
+    let union = a.offset.max(b.offset) - a.onset.min(b.onset);
+    let intersection = a.offset.min(b.offset) - a.onset.max(b.onset);
+    if union <= 0.0 {
+        0.0
+    } else {
+        (intersection / union).max(0.0)
+    }
+}
+
+/// Maximum one-to-one matching between `reference` and `estimated` events, where
+/// `hit(r, e)` tells whether the two can be matched. Returns the matched pairs.
+fn match_events(
+    reference: usize,
+    estimated: usize,
+    hit: impl Fn(usize, usize) -> bool,
+) -> Vec<(usize, usize)> {
+    let candidates: Vec<Vec<usize>> = (0..reference)
+        .map(|r| (0..estimated).filter(|&e| hit(r, e)).collect())
+        .collect();
+
+    // Kuhn's augmenting paths
+    fn augment(
+        r: usize,
+        candidates: &[Vec<usize>],
+        visited: &mut [bool],
+        owner: &mut [Option<usize>],
+    ) -> bool {
+        for &e in candidates[r].iter() {
+            if visited[e] {
+                continue;
+            }
+            visited[e] = true;
+            if owner[e].is_none_or(|other| augment(other, candidates, visited, owner)) {
+                owner[e] = Some(r);
+                return true;
+            }
+        }
+        false
+    }
+
+    let mut owner = vec![None; estimated];
+    for r in 0..reference {
+        let mut visited = vec![false; estimated];
+        augment(r, &candidates, &mut visited, &mut owner);
+    }
+
+    owner
+        .iter()
+        .enumerate()
+        .filter_map(|(e, r)| r.map(|r| (r, e)))
+        .collect()
+}
@@ -0,0 +1,237 @@
+use std::{error::Error, io::Write};
+
+use serde::Serialize;
+
+use crate::{
+    algorithms::{
+        bpm_detection::TempoCandidate, dynamics::NoteDynamics, pitch::PitchTrack, shared::OdfTiming,
+    },
+    evaluation::Scores,
+    notes::NoteEvent,
+    pipeline::Transcription,
+    tuning::Tuning,
+};
+
+/// Version of the record layouts below. Fields are only ever added at the end; any
+/// other change bumps it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A flat row of one analysis stage, written as a CSV line or a JSON object. Times are
+/// in seconds and frequencies in Hz.
+pub trait Record: Serialize {
+    /// Name of the stage, stored in the JSON document
+    const KIND: &'static str;
+    /// Column names, in field order
+    const FIELDS: &'static [&'static str];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct OnsetRecord {
+    pub time: f32,
+}
+
+/// One frame of the onset detection function
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct OdfRecord {
+    pub time: f32,
+    pub value: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TempoRecord {
+    pub bpm: f32,
+    pub strength: f32,
+}
+
+/// One frame of a pitch track. `f0` and `note` are empty (null) when the frame is not
+/// voiced enough.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PitchRecord {
+    pub time: f32,
+    pub f0: Option<f32>,
+    pub voiced_prob: f32,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NoteRecord {
+    pub onset: f32,
+    pub offset: f32,
+    /// Spelled name with octave, e.g. "F#4"
+    pub note: String,
+    /// Empty above G9
+    pub midi: Option<u8>,
+    /// Frequency of the note in the estimated tuning
+    pub freq: f32,
+    pub velocity: u8,
+    /// Dynamic marking, from "pp" to "ff"
+    pub dynamic: String,
+}
+
+/// Accuracy of one stage against a reference annotation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScoreRecord {
+    /// "onsets", "notes" or "notes-onset-only"
+    pub metric: String,
+    pub precision: f32,
+    pub recall: f32,
+    pub f_measure: f32,
+    /// Mean overlap of matched notes, empty for onsets
+    pub average_overlap_ratio: Option<f32>,
+}
+
+impl Record for OnsetRecord {
+    const KIND: &'static str = "onsets";
+    const FIELDS: &'static [&'static str] = &["time"];
+}
+
+impl Record for OdfRecord {
+    const KIND: &'static str = "odf";
+    const FIELDS: &'static [&'static str] = &["time", "value"];
+}
+
+impl Record for TempoRecord {
+    const KIND: &'static str = "tempo";
+    const FIELDS: &'static [&'static str] = &["bpm", "strength"];
+}
+
+impl Record for PitchRecord {
+    const KIND: &'static str = "pitch";
+    const FIELDS: &'static [&'static str] = &["time", "f0", "voiced_prob", "note"];
+}
+
+impl Record for NoteRecord {
+    const KIND: &'static str = "notes";
+    const FIELDS: &'static [&'static str] = &[
+        "onset", "offset", "note", "midi", "freq", "velocity", "dynamic",
+    ];
+}
+
+impl Record for ScoreRecord {
+    const KIND: &'static str = "evaluation";
+    const FIELDS: &'static [&'static str] = &[
+        "metric",
+        "precision",
+        "recall",
+        "f_measure",
+        "average_overlap_ratio",
+    ];
+}
+
+impl OnsetRecord {
+    pub fn from_times(times: &[f32]) -> Vec<Self> {
+        times.iter().map(|&time| OnsetRecord { time }).collect()
+    }
+}
+
+impl OdfRecord {
+    /// Records of an ODF with the time base `timing`
+    pub fn from_odf(odf: &[f32], timing: OdfTiming) -> Vec<Self> {
+        odf.iter()
+            .enumerate()
+            .map(|(i, &value)| OdfRecord {
+                time: timing.time(i),
+                value,
+            })
+            .collect()
+    }
+}
+
+impl From<TempoCandidate> for TempoRecord {
+    fn from(candidate: TempoCandidate) -> Self {
+        TempoRecord {
+            bpm: candidate.bpm,
+            strength: candidate.strength,
+        }
+    }
+}
+
+impl PitchRecord {
+    pub fn from_track(track: &PitchTrack, min_voiced_prob: f32, tuning: &Tuning) -> Vec<Self> {
+        track
+            .frames
+            .iter()
+            .map(|frame| {
+                let f0 = frame.f0.filter(|_| frame.voiced_prob >= min_voiced_prob);
+                PitchRecord {
+                    time: frame.time,
+                    f0,
+                    voiced_prob: frame.voiced_prob,
+                    note: f0.map(|f| tuning.note(f).to_string()),
+                }
+            })
+            .collect()
+    }
+}
+
+impl NoteRecord {
+    /// Record of `event`, with its note spelled as `name`
+    pub fn new(event: &NoteEvent, name: &str, dynamics: &NoteDynamics) -> Self {
+        NoteRecord {
+            onset: event.onset,
+            offset: event.offset,
+            note: name.to_string(),
+            midi: event.note.midi(),
+            freq: event.note.freq,
+            velocity: dynamics.velocity,
+            dynamic: dynamics.dynamic.to_string(),
+        }
+    }
+
+    /// Records of every note of `transcription`, spelled in its key
+    pub fn from_transcription(transcription: &Transcription) -> Vec<Self> {
+        let names = transcription.note_names();
+        transcription
+            .notes
+            .iter()
+            .zip(names.iter())
+            .zip(transcription.dynamics.iter())
+            .map(|((event, name), dynamics)| NoteRecord::new(event, name, dynamics))
+            .collect()
+    }
+}
+
+impl ScoreRecord {
+    pub fn new(metric: &str, scores: Scores, average_overlap_ratio: Option<f32>) -> Self {
+        ScoreRecord {
+            metric: metric.to_string(),
+            precision: scores.precision,
+            recall: scores.recall,
+            f_measure: scores.f_measure,
+            average_overlap_ratio,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Document<'a, R> {
+    schema_version: u32,
+    kind: &'static str,
+    records: &'a [R],
+}
+
+/// Writes `records` as a JSON document:
+/// `{"schema_version": 1, "kind": "onsets", "records": [{"time": 0.5}, ...]}`
+pub fn write_json<R: Record>(out: &mut dyn Write, records: &[R]) -> Result<(), Box<dyn Error>> {
+    let document = Document {
+        schema_version: SCHEMA_VERSION,
+        kind: R::KIND,
+        records,
+    };
+    serde_json::to_writer_pretty(&mut *out, &document)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Writes `records` as CSV with a header line, also when there are no records
+pub fn write_csv<R: Record>(out: &mut dyn Write, records: &[R]) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(out);
+    writer.write_record(R::FIELDS)?;
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
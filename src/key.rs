@@ -0,0 +1,105 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    notes::{Note, NoteName},
+    spelling::{Accidental, ParseNoteError, Spelling, fifths_position, parse_spelling},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Major,
+    Minor,
+}
+
+/// A major or minor key, used to pick the spelling of accidentals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub tonic: NoteName,
+    pub mode: Mode,
+}
+
+impl Key {
+    pub fn new(tonic: NoteName, mode: Mode) -> Self {
+        Key { tonic, mode }
+    }
+
+    pub fn major(tonic: NoteName) -> Self {
+        Key::new(tonic, Mode::Major)
+    }
+
+    pub fn minor(tonic: NoteName) -> Self {
+        Key::new(tonic, Mode::Minor)
+    }
+
+    /// Number of sharps (positive) or flats (negative) in the key signature, choosing
+    /// the enharmonic key with the fewest accidentals (sharps on a tie, e.g. F# major
+    /// rather than Gb major)
+    pub fn fifths(&self) -> i32 {
+        // Position of the tonic of the relative major on the line of fifths
+        let relative_major = match self.mode {
+            Mode::Major => self.tonic,
+            Mode::Minor => NoteName::from_pitch_class(self.tonic.pitch_class() + 3),
+        };
+        fifths_position(relative_major, -5)
+    }
+
+    /// Spelling of the tonic consistent with [`Key::fifths`]
+    pub fn tonic_spelling(&self) -> Spelling {
+        self.spell(self.tonic)
+    }
+
+    /// Spelling of `name` in this key. Diatonic notes follow the key signature and
+    /// chromatic notes take the spelling closest to it on the line of fifths: lowered
+    /// degrees and a raised fourth in major, raised sixth and seventh in minor. Double
+    /// accidentals are replaced by their single enharmonic.
+    pub fn spell(&self, name: NoteName) -> Spelling {
+        let low = match self.mode {
+            Mode::Major => self.fifths() - 5,
+            Mode::Minor => self.fifths() - 3,
+        };
+        let position = fifths_position(name, low);
+        let spelling = Spelling::from_fifths(position).unwrap();
+        match spelling.accidental {
+            Accidental::DoubleSharp => Spelling::from_fifths(position - 12).unwrap(),
+            Accidental::DoubleFlat => Spelling::from_fifths(position + 12).unwrap(),
+            _ => spelling,
+        }
+    }
+
+    /// `note` written with its spelling in this key, e.g. "Bb2"
+    pub fn spell_note(&self, note: &Note) -> String {
+        let spelling = self.spell(note.name);
+        format!("{}{}", spelling, spelling.octave(note))
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mode::Major => f.write_str("major"),
+            Mode::Minor => f.write_str("minor"),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.tonic_spelling(), self.mode)
+    }
+}
+
+impl FromStr for Key {
+    type Err = ParseNoteError;
+
+    /// Parses keys such as "Eb major", "C# minor", "F" or "Bbm"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseNoteError(s.to_string());
+        let (spelling, rest) = parse_spelling(s.trim()).ok_or_else(err)?;
+        let mode = match rest.trim().to_lowercase().as_str() {
+            "" | "maj" | "major" => Mode::Major,
+            "m" | "min" | "minor" => Mode::Minor,
+            _ => return Err(err()),
+        };
+        Ok(Key::new(spelling.name(), mode))
+    }
+}
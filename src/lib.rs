@@ -1,4 +1,21 @@
 pub mod algorithms;
+pub mod annotations;
+pub mod batch;
 pub mod charts;
+pub mod chords;
+pub mod config;
+pub mod evaluation;
+pub mod export;
+pub mod key;
 pub mod notes;
+pub mod pipeline;
 pub mod samples;
+pub mod scales;
+pub mod sonify;
+pub mod spelling;
+pub mod synth;
+pub mod temperament;
+pub mod tuner;
+pub mod tuning;
+
+pub use pipeline::{Transcriber, Transcription};
@@ -1,97 +1,362 @@
-use std::{ops::Deref, path::Path};
+mod cli;
 
-use pyin::{Framing, PYINExecutor, PadMode};
+use std::{
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use clap::Parser;
+use cli::{Cli, Command, OutputFormat};
 use transcriber::{
-    algorithms::{
-        bpm_detection::bpm,
-        onset_detection::StftBasedOnset,
-        peak_picking::{peak_picking, peak_picking_to_notes, peak_picking_to_seconds},
-        shared::standardize,
-    },
+    Transcriber, Transcription,
+    annotations::Annotation,
+    batch::{Batch, OutputLocation},
     charts::{plot, print_frequencies},
-    notes::Note,
-    samples::file_to_samples,
+    config::Config,
+    evaluation::{NoteMetrics, onset_scores},
+    export::{
+        NoteRecord, OdfRecord, OnsetRecord, PitchRecord, Record, ScoreRecord, TempoRecord,
+        write_csv, write_json,
+    },
+    samples::{Samples, file_to_samples, samples_to_file},
+    sonify::Sonifier,
+    tuner::{Search, Tuner, load_examples},
 };
 
-fn main() {
-    let samples = file_to_samples(&Path::new("audio/test5.wav"));
-    plot(&samples, "samples").unwrap();
-    let hop_size = 441;
-    let onset = StftBasedOnset::new(&samples, 2048, hop_size);
-    let mut cd = onset.rcd();
-
-    let odf_rate = samples.spec.sample_rate as f32 / hop_size as f32;
-    let bpm = bpm(&cd, odf_rate);
-    println!("BPM: {}", bpm);
-
-    standardize(&mut cd);
-    plot(&cd, "odf").unwrap();
-
-    let onsets = peak_picking(&mut cd, 3, 3, 0.5, 0.4);
-
-    plot(
-        &(onsets
-            .iter()
-            .map(|f| if *f { 0.1 } else { 0.0 })
-            .collect::<Vec<f32>>()),
-        "onsets",
-    )
-    .unwrap();
-
-    let onset_seconds =
-        peak_picking_to_seconds(&onsets, samples.spec.duration_milis as f32 / 1000.0);
-
-    println!("Onsets: {:?}", onset_seconds);
-
-    let fmin = 40f64; // minimum frequency in Hz
-    let fmax = 600f64; // maximum frequency in Hz
-    let sr = 44100; // sampling rate of audio data in Hz
-    let frame_length = 4096; // frame length in samples
-    let (win_length, hop_length, resolution) = (None, None, Some(0.05)); // None to use default values
-    let mut pyin_exec = PYINExecutor::new(
-        fmin,
-        fmax,
-        sr,
-        frame_length,
-        win_length,
-        hop_length,
-        resolution,
-    );
-
-    let wav: Vec<f64> = samples.deref().iter().map(|f| *f as f64).collect();
-    let fill_unvoiced = f64::NAN;
-    let framing = Framing::Center(PadMode::Constant(0.)); // Zero-padding is applied on both sides of the signal. (only if cetner is true)
-
-    let (_timestamp, f0, _voiced_flag, voiced_prob) = pyin_exec.pyin(&wav, fill_unvoiced, framing);
-    let notes = &f0
-        .iter()
-        .enumerate()
-        .map(|(i, f)| {
-            if f.is_nan() || voiced_prob[i] < 0.7 {
-                None
+/// Peak amplitude of the onset clicks of the sonify command
+const CLICK_LEVEL: f32 = 0.5;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    let config = cli.config()?;
+    match &cli.output {
+        // Buffered so that a failed run leaves a previous output file in place
+        Some(path) => {
+            let mut buffer = Vec::new();
+            run(&cli, config, &mut buffer)?;
+            fs::write(path, buffer)?;
+        }
+        None => run(&cli, config, &mut io::stdout().lock())?,
+    }
+    Ok(())
+}
+
+/// Runs the command of `cli` with `config`, which already has its flags applied
+fn run(cli: &Cli, mut config: Config, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    match &cli.command {
+        Command::Onsets { input, .. } => {
+            let samples = load(input)?;
+            let seconds = Transcriber::new(config)?.detect_onsets(&samples).times;
+            match cli.format {
+                OutputFormat::Text => writeln!(out, "Onsets: {:?}", seconds)?,
+                format => export(out, format, &OnsetRecord::from_times(&seconds))?,
+            }
+        }
+        Command::Odf { input, .. } => {
+            let samples = load(input)?;
+            let onsets = Transcriber::new(config)?.detect_onsets(&samples);
+            let records = OdfRecord::from_odf(&onsets.odf, onsets.timing);
+            match cli.format {
+                OutputFormat::Text => {
+                    for record in records {
+                        writeln!(out, "{:.3}s {:.3}", record.time, record.value)?;
+                    }
+                }
+                format => export(out, format, &records)?,
+            }
+        }
+        Command::Tempo { input, .. } => {
+            let samples = load(input)?;
+            let (bpm, candidates) = Transcriber::new(config)?.estimate_tempo(&samples);
+            match cli.format {
+                OutputFormat::Text => {
+                    writeln!(out, "BPM: {}", bpm)?;
+                    for candidate in candidates {
+                        writeln!(
+                            out,
+                            "Candidate: {:.2} BPM (strength {:.2})",
+                            candidate.bpm, candidate.strength
+                        )?;
+                    }
+                }
+                format => {
+                    let records: Vec<TempoRecord> =
+                        candidates.into_iter().map(TempoRecord::from).collect();
+                    export(out, format, &records)?
+                }
+            }
+        }
+        Command::Pitch { input, .. } => {
+            let samples = load(input)?;
+            let min_voiced_prob = config.pitch.min_voiced_prob as f32;
+            let (track, tuning) = Transcriber::new(config)?.detect_pitch(&samples);
+            if cli.format != OutputFormat::Text {
+                let records = PitchRecord::from_track(&track, min_voiced_prob, &tuning);
+                export(out, cli.format, &records)?;
+            } else {
+                writeln!(
+                    out,
+                    "Tuning: A4 = {:.2} Hz ({:+.1} cents)",
+                    tuning.a4,
+                    tuning.cents()
+                )?;
+                for frame in track.frames.iter() {
+                    match frame.f0.filter(|_| frame.voiced_prob >= min_voiced_prob) {
+                        Some(f0) => writeln!(
+                            out,
+                            "{:.3}s {:.2} Hz {} (p = {:.2})",
+                            frame.time,
+                            f0,
+                            tuning.note(f0),
+                            frame.voiced_prob
+                        )?,
+                        None => {
+                            writeln!(out, "{:.3}s - (p = {:.2})", frame.time, frame.voiced_prob)?
+                        }
+                    }
+                }
+            }
+        }
+        Command::Transcribe { input, .. } => {
+            let transcription = Transcriber::new(config)?.transcribe(&load(input)?);
+            write_transcription(&transcription, cli.format, out)?;
+        }
+        Command::Evaluate {
+            input,
+            reference,
+            reference_format,
+            onset_tolerance,
+            ..
+        } => {
+            let reference = Annotation::from_file(reference, *reference_format)?;
+            let transcription = Transcriber::new(config)?.transcribe(&load(input)?);
+
+            let window = onset_tolerance.unwrap_or(NoteMetrics::default().onset_tolerance);
+            let onset = onset_scores(&reference.onsets(), &transcription.onsets, window);
+            let mut records = vec![ScoreRecord::new("onsets", onset, None)];
+            if let Some(reference) = reference.notes() {
+                let metrics = [
+                    ("notes", NoteMetrics::default()),
+                    ("notes-onset-only", NoteMetrics::onset_only()),
+                ];
+                for (name, metrics) in metrics {
+                    let metrics = NoteMetrics {
+                        onset_tolerance: window,
+                        ..metrics
+                    };
+                    let scores = metrics.evaluate(reference, &transcription.notes);
+                    records.push(ScoreRecord::new(
+                        name,
+                        scores.scores,
+                        Some(scores.average_overlap_ratio),
+                    ));
+                }
+            }
+
+            match cli.format {
+                OutputFormat::Text => {
+                    for record in records {
+                        write!(
+                            out,
+                            "{}: P = {:.3} R = {:.3} F = {:.3}",
+                            record.metric, record.precision, record.recall, record.f_measure
+                        )?;
+                        match record.average_overlap_ratio {
+                            Some(overlap) => writeln!(out, " overlap = {:.3}", overlap)?,
+                            None => writeln!(out)?,
+                        }
+                    }
+                }
+                format => export(out, format, &records)?,
+            }
+        }
+        Command::Sonify {
+            input,
+            wav,
+            pitch_track,
+            balance,
+            clicks,
+            ..
+        } => {
+            let min_voiced_prob = config.pitch.min_voiced_prob as f32;
+            let samples = load(input)?;
+            let transcription = Transcriber::new(config)?.transcribe(&samples);
+
+            let click_level = if *clicks { CLICK_LEVEL } else { 0.0 };
+            let sonifier = Sonifier::new(balance.unwrap_or(0.0), click_level);
+            let rendered = if *pitch_track {
+                sonifier.pitch_track(
+                    &samples,
+                    &transcription.pitch_track,
+                    min_voiced_prob,
+                    &transcription.onsets,
+                )
             } else {
-                Some(Note::from(*f as f32))
+                sonifier.notes(&samples, &transcription.notes, &transcription.onsets)
+            };
+            samples_to_file(&rendered, wav)?;
+            writeln!(out, "Written to {}", wav.display())?;
+        }
+        Command::Batch {
+            input,
+            out_dir,
+            jobs,
+            no_recursive,
+            ..
+        } => {
+            if !input.is_dir() {
+                return Err(format!("no such directory: {}", input.display()).into());
             }
-        })
-        .collect::<Vec<Option<Note>>>();
+            let location = match out_dir {
+                Some(dir) => OutputLocation::Mirror(dir.clone()),
+                None => OutputLocation::NextToInput,
+            };
+            let mut batch = Batch::new(location, cli.format.extension());
+            batch.recursive = !no_recursive;
+            if let Some(jobs) = jobs {
+                batch.workers = *jobs;
+            }
+            let transcriber = Transcriber::new(config)?;
+            let report = batch.run(input, |path| {
+                let transcription = transcriber.transcribe(&load(path)?);
+                let mut text = Vec::new();
+                write_transcription(&transcription, cli.format, &mut text)?;
+                Ok(String::from_utf8(text)?)
+            })?;
+            writeln!(out, "{}", report)?;
+        }
+        Command::Tune {
+            input,
+            save,
+            random,
+            seed,
+            reference_format,
+            onset_tolerance,
+            ..
+        } => {
+            if !input.is_dir() {
+                return Err(format!("no such directory: {}", input.display()).into());
+            }
+            let loaded = load_examples(input, *reference_format)?;
+            for (path, error) in loaded.skipped.iter() {
+                writeln!(out, "skipped {}: {}", path.display(), error)?;
+            }
+            let examples = loaded.examples;
+            if examples.is_empty() {
+                return Err(format!("no annotated WAV file in {}", input.display()).into());
+            }
+            let search = match random {
+                Some(trials) => Search::Random {
+                    trials: *trials,
+                    seed: seed.unwrap_or(0),
+                },
+                None => Search::Grid,
+            };
+            let mut tuner = Tuner::new(search);
+            if let Some(tolerance) = onset_tolerance {
+                tuner.tolerance = *tolerance;
+            }
+
+            let result = tuner.tune(&config.onsets, &examples);
+            config.onsets = result.config.clone();
+            config.save(save)?;
+            writeln!(out, "Files: {}", examples.len())?;
+            writeln!(out, "Best: {}", result)?;
+            writeln!(out, "Written to {}", save.display())?;
+        }
+        Command::Plot { input, .. } => {
+            let samples = load(input)?;
+            fs::create_dir_all("charts")?;
+            plot(&samples, "samples")?;
+            let min_voiced_prob = config.pitch.min_voiced_prob as f32;
+            let transcriber = Transcriber::new(config)?;
+            let onsets = transcriber.detect_onsets(&samples);
+            plot(&onsets.odf, "odf")?;
+            let markers: Vec<f32> = onsets
+                .peaks
+                .iter()
+                .map(|p| if *p { 0.1 } else { 0.0 })
+                .collect();
+            plot(&markers, "onsets")?;
+            let (track, _) = transcriber.detect_pitch(&samples);
+            let frequencies: Vec<(f32, bool)> = track
+                .f0(min_voiced_prob)
+                .iter()
+                .map(|f| (f.unwrap_or(0.0), f.is_some()))
+                .collect();
+            print_frequencies(&frequencies)?;
+            writeln!(out, "Charts written to charts/")?;
+        }
+    }
+
+    out.flush()?;
+    Ok(())
+}
 
-    // for note in notes {
-    //     println!("Note: {:?}", note);
-    // }
+fn load(path: &Path) -> Result<Samples, Box<dyn Error>> {
+    if !path.is_file() {
+        return Err(format!("no such file: {}", path.display()).into());
+    }
+    Ok(file_to_samples(path)?)
+}
 
-    let all_notes = peak_picking_to_notes(&onsets, &notes);
-    for note in all_notes.iter() {
-        println!("Note: {:?}", note);
+fn write_transcription(
+    transcription: &Transcription,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn Error>> {
+    if format != OutputFormat::Text {
+        return export(out, format, &NoteRecord::from_transcription(transcription));
     }
 
-    print_frequencies(
-        &notes
-            .iter()
-            .map(|n| match n {
-                Some(n) => (n.freq, true),
-                None => (0.0, false),
-            })
-            .collect::<Vec<(f32, bool)>>(),
-    )
-    .unwrap();
+    let tuning = transcription.tuning;
+    writeln!(
+        out,
+        "Tuning: A4 = {:.2} Hz ({:+.1} cents)",
+        tuning.a4,
+        tuning.cents()
+    )?;
+    if let Some(key) = transcription.key {
+        writeln!(
+            out,
+            "Key: {} (r = {:.2}, confidence {:.2})",
+            key.key, key.correlation, key.confidence
+        )?;
+    }
+    writeln!(out, "Tempo: {:.1} BPM", transcription.tempo)?;
+
+    let names = transcription.note_names();
+    for (i, event) in transcription.notes.iter().enumerate() {
+        let dynamics = transcription.dynamics[i];
+        write!(
+            out,
+            "Note: {} {:.2}s - {:.2}s {} (velocity {})",
+            names[i], event.onset, event.offset, dynamics.dynamic, dynamics.velocity
+        )?;
+        match &transcription.contours[i] {
+            Some(contour) => writeln!(
+                out,
+                " mean: {:+.1} cents drift: {:+.1} cents/s glide: {:+.1} -> {:+.1}",
+                contour.mean, contour.drift, contour.glide_start, contour.glide_end
+            )?,
+            None => writeln!(out)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes `records` in a machine-readable format
+fn export<R: Record>(
+    out: &mut dyn Write,
+    format: OutputFormat,
+    records: &[R],
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        OutputFormat::Csv => write_csv(out, records),
+        OutputFormat::Json => write_json(out, records),
+        OutputFormat::Text => unreachable!("text output is written by each command"),
+    }
 }
@@ -1,6 +1,21 @@
-use std::sync::LazyLock;
+use std::{
+    cmp::Ordering,
+    error::Error,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{Add, Sub},
+    str::FromStr,
+    sync::LazyLock,
+};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+use crate::{
+    spelling::{ParseNoteError, parse_spelling},
+    tuning::Tuning,
+};
+
+/// A 12-TET note. Two notes are equal when they share name and octave, regardless of
+/// the reference they were tuned to.
+#[derive(Debug, Clone, Copy)]
 pub struct Note {
     pub freq: f32,
     pub octave: usize,
@@ -11,13 +26,233 @@ impl Note {
     pub fn new(freq: f32, octave: usize, name: NoteName) -> Self {
         Self { freq, octave, name }
     }
+
+    /// Signed distance in cents from this note to `freq`
+    pub fn cents(&self, freq: f32) -> f32 {
+        1200.0 * (freq / self.freq).log2()
+    }
+
+    /// Number of semitones above C0
+    pub fn semitones(&self) -> i32 {
+        self.octave as i32 * 12 + self.name.pitch_class() as i32
+    }
+
+    /// MIDI note number, where C4 = 60. Always `Some` for the notes of the range, C0 (12)
+    /// to B8 (119); `None` only for a note built with an octave above G9, the highest
+    /// MIDI note.
+    pub fn midi(&self) -> Option<u8> {
+        u8::try_from(self.semitones() + MIDI_C0)
+            .ok()
+            .filter(|midi| *midi <= 127)
+    }
+
+    /// The note `semitones` above (or below, if negative) this one, keeping its tuning.
+    /// `None` if the result would fall below C0.
+    pub fn transpose(&self, semitones: i32) -> Option<Note> {
+        let index = self.semitones() + semitones;
+        if index < 0 {
+            return None;
+        }
+        Some(Note::new(
+            self.freq * 2.0f32.powf(semitones as f32 / 12.0),
+            index as usize / 12,
+            NoteName::from_pitch_class(index as u8),
+        ))
+    }
+
+    /// Interval from this note up to `other`
+    pub fn interval(&self, other: &Note) -> Interval {
+        Interval::new(other.semitones() - self.semitones())
+    }
+}
+
+impl PartialEq for Note {
+    fn eq(&self, other: &Self) -> bool {
+        self.semitones() == other.semitones()
+    }
+}
+
+impl Eq for Note {}
+
+impl PartialOrd for Note {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Note {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.semitones().cmp(&other.semitones())
+    }
+}
+
+impl Hash for Note {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.semitones().hash(state);
+    }
+}
+
+impl Add<i32> for Note {
+    type Output = Note;
+
+    /// Transposes up by `rhs` semitones. Panics if the result falls below C0.
+    fn add(self, rhs: i32) -> Note {
+        self.transpose(rhs).expect("note transposed below C0")
+    }
+}
+
+impl Sub<i32> for Note {
+    type Output = Note;
+
+    /// Transposes down by `rhs` semitones. Panics if the result falls below C0.
+    fn sub(self, rhs: i32) -> Note {
+        self.transpose(-rhs).expect("note transposed below C0")
+    }
 }
 
+impl Add<Interval> for Note {
+    type Output = Note;
+
+    fn add(self, rhs: Interval) -> Note {
+        self + rhs.semitones
+    }
+}
+
+impl Sub for Note {
+    type Output = Interval;
+
+    /// Interval from `rhs` up to `self`
+    fn sub(self, rhs: Note) -> Interval {
+        rhs.interval(&self)
+    }
+}
+
+impl fmt::Display for Note {
+    /// Scientific pitch notation with sharps, e.g. "C#4"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.name, self.octave)
+    }
+}
+
+impl FromStr for Note {
+    type Err = ParseNoteError;
+
+    /// Parses scientific pitch notation such as "C#4", "Db4" or "Bb2" into a 12-TET
+    /// note with A4 = 440 Hz
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseNoteError(s.to_string());
+        let (spelling, octave) = parse_spelling(s.trim()).ok_or_else(err)?;
+        let octave: i32 = octave.parse().map_err(|_| err())?;
+        let semitones = octave * 12 + spelling.offset();
+        if !(0..=B8_INDEX).contains(&semitones) {
+            return Err(err());
+        }
+        Ok(Note::new(
+            Tuning::default().freq(semitones),
+            semitones as usize / 12,
+            NoteName::from_pitch_class(semitones as u8),
+        ))
+    }
+}
+
+/// MIDI note number of C0
+const MIDI_C0: i32 = 12;
+
+/// MIDI note number outside of the C0..=B8 range covered by [`Note`]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MidiRangeError(pub i32);
+
+impl fmt::Display for MidiRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MIDI note {} is out of range", self.0)
+    }
+}
+
+impl Error for MidiRangeError {}
+
+impl TryFrom<u8> for Note {
+    type Error = MidiRangeError;
+
+    /// 12-TET note with A4 = 440 Hz
+    fn try_from(midi: u8) -> Result<Self, Self::Error> {
+        let semitones = midi as i32 - MIDI_C0;
+        if !(0..=B8_INDEX).contains(&semitones) {
+            return Err(MidiRangeError(midi as i32));
+        }
+        Ok(Note::new(
+            Tuning::default().freq(semitones),
+            semitones as usize / 12,
+            NoteName::from_pitch_class(semitones as u8),
+        ))
+    }
+}
+
+impl TryFrom<Note> for u8 {
+    type Error = MidiRangeError;
+
+    fn try_from(note: Note) -> Result<Self, Self::Error> {
+        note.midi()
+            .ok_or(MidiRangeError(note.semitones() + MIDI_C0))
+    }
+}
+
+/// Distance between two notes in semitones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Interval {
+    pub semitones: i32,
+}
+
+impl Interval {
+    pub fn new(semitones: i32) -> Self {
+        Interval { semitones }
+    }
+
+    /// The interval reduced to less than an octave, keeping its direction
+    pub fn simple(&self) -> Interval {
+        Interval::new(self.semitones % 12)
+    }
+
+    /// Whole octaves spanned by the interval
+    pub fn octaves(&self) -> i32 {
+        self.semitones.abs() / 12
+    }
+
+    /// Short name of the simple interval, e.g. "m3" or "P5", whatever its direction
+    pub fn name(&self) -> &'static str {
+        const NAMES: [&str; 12] = [
+            "P1", "m2", "M2", "m3", "M3", "P4", "TT", "P5", "m6", "M6", "m7", "M7",
+        ];
+        NAMES[self.simple().semitones.unsigned_abs() as usize]
+    }
+}
+
+/// A note with its position in time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteEvent {
+    /// Start of the note in seconds
+    pub onset: f32,
+    /// End of the note in seconds
+    pub offset: f32,
+    pub note: Note,
+}
+
+impl NoteEvent {
+    pub fn new(onset: f32, offset: f32, note: Note) -> Self {
+        Self {
+            onset,
+            offset,
+            note,
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.offset - self.onset
+    }
+}
+
+/// Pitch class of a note, in octave order starting at C
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum NoteName {
-    A,
-    ASharp,
-    B,
     C,
     CSharp,
     D,
@@ -27,60 +262,103 @@ pub enum NoteName {
     FSharp,
     G,
     GSharp,
+    A,
+    ASharp,
+    B,
 }
 
-pub fn all_notes() -> Vec<Note> {
-    let mut notes = Vec::new();
-    let mut base_freq = 16.35;
-    for octave in 0..9 {
-        for name in &[
-            NoteName::C,
-            NoteName::CSharp,
-            NoteName::D,
-            NoteName::DSharp,
-            NoteName::E,
-            NoteName::F,
-            NoteName::FSharp,
-            NoteName::G,
-            NoteName::GSharp,
-            NoteName::A,
-            NoteName::ASharp,
-            NoteName::B,
-        ] {
-            if octave == 0 && *name == NoteName::C {
-                notes.push(Note::new(base_freq, octave, *name));
-                continue;
-            }
-            let freq = base_freq * 2.0f32.powf(1.0 as f32 / 12.0);
-            notes.push(Note::new(freq, octave, *name));
-
-            base_freq = freq;
-        }
+impl NoteName {
+    /// Semitones above C, in 0..12
+    pub fn pitch_class(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Note name of a pitch class, wrapping around every 12 semitones
+    pub fn from_pitch_class(pitch_class: u8) -> Self {
+        NOTE_NAMES[pitch_class as usize % 12]
     }
-    notes
 }
 
-pub static ALL_NOTES: LazyLock<Vec<Note>> = LazyLock::new(|| all_notes());
+/// Note names in octave order, starting at C
+pub const NOTE_NAMES: [NoteName; 12] = [
+    NoteName::C,
+    NoteName::CSharp,
+    NoteName::D,
+    NoteName::DSharp,
+    NoteName::E,
+    NoteName::F,
+    NoteName::FSharp,
+    NoteName::G,
+    NoteName::GSharp,
+    NoteName::A,
+    NoteName::ASharp,
+    NoteName::B,
+];
+
+/// Semitones from C0 to A4
+pub const A4_INDEX: i32 = 57;
+
+/// Semitones from C0 to B8, the highest note
+pub const B8_INDEX: i32 = 107;
+
+/// C0 to B8 in 12-TET with A4 = 440 Hz
+pub fn all_notes() -> Vec<Note> {
+    tuned_notes(440.0)
+}
+
+/// C0 to B8 in 12-TET with the given A4 reference
+pub fn tuned_notes(a4: f32) -> Vec<Note> {
+    let tuning = Tuning::new(a4);
+    (0..=B8_INDEX)
+        .map(|i| Note::new(tuning.freq(i), i as usize / 12, NOTE_NAMES[i as usize % 12]))
+        .collect()
+}
+
+/// C0 to B8 in 12-TET with A4 = 440 Hz, computed once
+pub static ALL_NOTES: LazyLock<Vec<Note>> = LazyLock::new(all_notes);
 
 impl From<f32> for Note {
+    /// Nearest 12-TET note with A4 = 440 Hz
     fn from(value: f32) -> Self {
-        let notes = &ALL_NOTES;
-        let mut start = 0;
-        let mut end = notes.len();
-        while end - start > 1 {
-            let mid = (start + end) / 2;
-            if notes[mid].freq < value {
-                start = mid;
-            } else {
-                end = mid;
-            }
-        }
-        let s = &notes[start];
-        let e = &notes[end];
-        if (s.freq - value).abs() < (e.freq - value).abs() {
-            *s
-        } else {
-            *e
-        }
+        Tuning::default().note(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_range_frequencies_clamp_to_the_range() {
+        assert_eq!(Note::from(0.0), "C0".parse().unwrap());
+        assert_eq!(Note::from(f32::INFINITY), "B8".parse().unwrap());
+    }
+
+    #[test]
+    fn notes_compare_and_hash_by_name_and_octave() {
+        use std::collections::HashSet;
+
+        let concert = Note::new(440.0, 4, NoteName::A);
+        let baroque = Note::new(415.3, 4, NoteName::A);
+        assert_eq!(concert, baroque);
+        assert_eq!(concert.cmp(&baroque), Ordering::Equal);
+        assert_eq!(HashSet::from([concert, baroque]).len(), 1);
+        assert_ne!(concert, Note::new(440.0, 5, NoteName::A));
+
+        assert_eq!(
+            NoteEvent::new(0.0, 1.0, concert),
+            NoteEvent::new(0.0, 1.0, baroque)
+        );
+        assert_ne!(
+            NoteEvent::new(0.0, 1.0, concert),
+            NoteEvent::new(0.0, 0.5, baroque)
+        );
+    }
+
+    #[test]
+    fn every_note_of_the_range_has_a_midi_number() {
+        let (lowest, highest): (Note, Note) = ("C0".parse().unwrap(), "B8".parse().unwrap());
+        assert_eq!((lowest.midi(), highest.midi()), (Some(12), Some(119)));
+        assert_eq!(Note::new(0.0, 9, NoteName::GSharp).midi(), None);
     }
 }
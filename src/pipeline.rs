@@ -0,0 +1,360 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    algorithms::{
+        bpm_detection::{TempoCandidate, beat_grid, bpm, tempo_candidates},
+        contour::PitchContour,
+        dynamics::{DynamicsEstimator, NoteDynamics},
+        key_detection::{KeyEstimate, detect_key},
+        peak_picking::peak_times,
+        pitch::PitchTrack,
+        shared::{OdfTiming, rms_envelope, standardize},
+    },
+    config::{Config, ConfigError, Preset},
+    notes::NoteEvent,
+    samples::Samples,
+    tuning::Tuning,
+};
+
+/// Runs the whole analysis of a recording: onsets, tempo and beats, pitch track, note
+/// segmentation, key and dynamics. Built from a [`Config`] or a [`Preset`]; the other
+/// settings are chained, e.g. `Transcriber::from(Preset::Voice).tempo_candidates(3)`.
+#[derive(Debug, Clone)]
+pub struct Transcriber {
+    config: Config,
+    dynamics: DynamicsEstimator,
+    tempo_candidates: usize,
+}
+
+/// Result of [`Transcriber::transcribe`]
+#[derive(Debug, Clone)]
+pub struct Transcription {
+    pub notes: Vec<NoteEvent>,
+    /// Dynamics of every note, in the same order
+    pub dynamics: Vec<NoteDynamics>,
+    /// Pitch contour of every note, `None` if it has no voiced frame
+    pub contours: Vec<Option<PitchContour>>,
+    pub key: Option<KeyEstimate>,
+    pub tuning: Tuning,
+    /// Tempo in BPM, 0 if the recording has no periodicity
+    pub tempo: f32,
+    /// Alternative tempi, strongest first
+    pub tempo_candidates: Vec<TempoCandidate>,
+    /// Beat times in seconds
+    pub beats: Vec<f32>,
+    /// Onset times in seconds
+    pub onsets: Vec<f32>,
+    pub pitch_track: PitchTrack,
+    pub diagnostics: Diagnostics,
+}
+
+/// Intermediate results, mostly useful to plot or debug a transcription
+#[derive(Debug, Clone)]
+pub struct Diagnostics {
+    /// Standardized onset detection function
+    pub odf: Vec<f32>,
+    /// Time base of the ODF. The energy envelope has the same frame rate, with frame
+    /// `i` centred at `i * hop_size` samples.
+    pub timing: OdfTiming,
+    /// Onset frames of the ODF
+    pub peaks: Vec<bool>,
+    /// RMS envelope, used to split repeated notes
+    pub energy: Vec<f32>,
+    /// Length of the recording in seconds
+    pub duration: f32,
+    /// Time spent in every stage
+    pub timings: Timings,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    pub onsets: Duration,
+    pub tempo: Duration,
+    pub pitch: Duration,
+    pub notes: Duration,
+    pub total: Duration,
+}
+
+/// Onset detection function along with its peaks
+#[derive(Debug, Clone)]
+pub struct Onsets {
+    /// Standardized onset detection function
+    pub odf: Vec<f32>,
+    pub timing: OdfTiming,
+    pub peaks: Vec<bool>,
+    /// Onset times in seconds
+    pub times: Vec<f32>,
+}
+
+impl Default for Transcriber {
+    fn default() -> Self {
+        Transcriber {
+            config: Config::default(),
+            dynamics: DynamicsEstimator::default(),
+            tempo_candidates: 5,
+        }
+    }
+}
+
+impl From<Preset> for Transcriber {
+    fn from(preset: Preset) -> Self {
+        Transcriber::new(preset.config()).expect("built-in presets are valid")
+    }
+}
+
+impl Transcriber {
+    /// Fails if `config` does not pass [`Config::validate`]
+    pub fn new(config: Config) -> Result<Self, ConfigError> {
+        config.validate()?;
+        Ok(Transcriber {
+            config,
+            ..Default::default()
+        })
+    }
+
+    pub fn dynamics(mut self, dynamics: DynamicsEstimator) -> Self {
+        self.dynamics = dynamics;
+        self
+    }
+
+    /// Number of tempo candidates kept in the transcription
+    pub fn tempo_candidates(mut self, count: usize) -> Self {
+        self.tempo_candidates = count;
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Onset detection function, its peaks and the onset times
+    pub fn detect_onsets(&self, samples: &Samples) -> Onsets {
+        let odf = self.config.onsets.detection_function(samples);
+        self.onsets(odf, samples.spec.sample_rate)
+    }
+
+    /// Tempo in BPM and the strongest alternative tempi
+    pub fn estimate_tempo(&self, samples: &Samples) -> (f32, Vec<TempoCandidate>) {
+        let odf = self.config.onsets.detection_function(samples);
+        self.tempo(&odf, samples.spec.sample_rate)
+    }
+
+    /// Standardizes the raw `odf` and picks its peaks
+    fn onsets(&self, mut odf: Vec<f32>, sample_rate: u32) -> Onsets {
+        standardize(&mut odf);
+        let peaks = self.config.onsets.peaks(&mut odf);
+        let timing = self.config.onsets.timing(sample_rate);
+        let times = peak_times(&peaks, timing);
+        Onsets {
+            odf,
+            timing,
+            peaks,
+            times,
+        }
+    }
+
+    /// Tempo of the raw `odf`
+    fn tempo(&self, odf: &[f32], sample_rate: u32) -> (f32, Vec<TempoCandidate>) {
+        let odf_rate = self.config.onsets.timing(sample_rate).rate();
+        (
+            bpm(odf, odf_rate),
+            tempo_candidates(odf, odf_rate, self.tempo_candidates),
+        )
+    }
+
+    /// Pitch track, along with the configured or estimated tuning
+    pub fn detect_pitch(&self, samples: &Samples) -> (PitchTrack, Tuning) {
+        let track = self.config.pitch.detector().detect(samples);
+        let tuning = self.config.pitch.tuning(&track);
+        (track, tuning)
+    }
+
+    pub fn transcribe(&self, samples: &Samples) -> Transcription {
+        let start = Instant::now();
+        let mut timings = Timings::default();
+
+        let sample_rate = samples.spec.sample_rate;
+        let odf = self.config.onsets.detection_function(samples);
+        let onsets = self.onsets(odf.clone(), sample_rate);
+        timings.onsets = start.elapsed();
+        if onsets.odf.is_empty() {
+            return self.empty_transcription(samples, onsets, timings);
+        }
+
+        let stage = Instant::now();
+        let (tempo, candidates) = self.tempo(&odf, sample_rate);
+        let beats = beat_grid(&onsets.odf, onsets.timing, tempo);
+        timings.tempo = stage.elapsed();
+
+        let stage = Instant::now();
+        let (track, tuning) = self.detect_pitch(samples);
+        timings.pitch = stage.elapsed();
+
+        let stage = Instant::now();
+        let onset_config = &self.config.onsets;
+        let energy = rms_envelope(samples, onset_config.frame_size, onset_config.hop_size);
+        let mut notes =
+            self.config
+                .note_tracker()
+                .track(&track, &onsets.odf, onsets.timing, &tuning);
+        if self.config.notes.split {
+            let splitter = self.config.note_splitter();
+            notes = splitter.split_legato(&notes, &track, &tuning);
+            notes = splitter.split_repeated(&notes, &energy, onsets.timing.rate());
+        }
+        let key = detect_key(&notes);
+        let dynamics = self.dynamics.estimate(samples, &notes);
+        let min_voiced_prob = self.config.pitch.min_voiced_prob as f32;
+        let contours = notes
+            .iter()
+            .map(|note| PitchContour::new(note, &track, min_voiced_prob))
+            .collect();
+        timings.notes = stage.elapsed();
+        timings.total = start.elapsed();
+
+        Transcription {
+            notes,
+            dynamics,
+            contours,
+            key,
+            tuning,
+            tempo,
+            tempo_candidates: candidates,
+            beats,
+            onsets: onsets.times,
+            pitch_track: track,
+            diagnostics: Diagnostics {
+                odf: onsets.odf,
+                timing: onsets.timing,
+                peaks: onsets.peaks,
+                energy,
+                duration: duration(samples),
+                timings,
+            },
+        }
+    }
+
+    /// Transcription of a recording too short for a single ODF value
+    fn empty_transcription(
+        &self,
+        samples: &Samples,
+        onsets: Onsets,
+        mut timings: Timings,
+    ) -> Transcription {
+        let track = PitchTrack::new(
+            samples.spec.sample_rate,
+            self.config.pitch.hop_size(),
+            Vec::new(),
+        );
+        timings.total = timings.onsets;
+        Transcription {
+            notes: Vec::new(),
+            dynamics: Vec::new(),
+            contours: Vec::new(),
+            key: None,
+            tuning: self.config.pitch.tuning(&track),
+            tempo: 0.0,
+            tempo_candidates: Vec::new(),
+            beats: Vec::new(),
+            onsets: Vec::new(),
+            pitch_track: track,
+            diagnostics: Diagnostics {
+                odf: onsets.odf,
+                timing: onsets.timing,
+                peaks: onsets.peaks,
+                energy: Vec::new(),
+                duration: duration(samples),
+                timings,
+            },
+        }
+    }
+}
+
+impl Transcription {
+    /// Name of every note, spelled in the detected key if there is one
+    pub fn note_names(&self) -> Vec<String> {
+        self.notes
+            .iter()
+            .map(|event| match self.key {
+                Some(key) => key.key.spell_note(&event.note),
+                None => event.note.to_string(),
+            })
+            .collect()
+    }
+}
+
+fn duration(samples: &Samples) -> f32 {
+    samples.spec.duration_milis / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::DetectorKind,
+        notes::Note,
+        synth::{Envelope, Synth, Waveform},
+    };
+
+    /// One minute of short sawtooth clicks at 120 BPM, starting at 0.25 s
+    fn click_track() -> (Samples, Vec<f32>) {
+        let note: Note = "A4".parse().unwrap();
+        let times: Vec<f32> = (0..120).map(|i| 0.25 + i as f32 * 0.5).collect();
+        let clicks: Vec<NoteEvent> = times
+            .iter()
+            .map(|&t| NoteEvent::new(t, t + 0.02, note))
+            .collect();
+        let synth = Synth {
+            envelope: Envelope {
+                attack: 0.001,
+                decay: 0.01,
+                sustain: 0.3,
+                release: 0.01,
+            },
+            ..Synth::new(Waveform::sawtooth(8))
+        };
+        (synth.render(&clicks), times)
+    }
+
+    #[test]
+    fn onsets_tempo_and_beats_share_a_time_base() {
+        let (samples, clicks) = click_track();
+        let transcriber = Transcriber::default();
+        let nearest = |time: f32| {
+            clicks
+                .iter()
+                .map(|click| (click - time).abs())
+                .fold(f32::INFINITY, f32::min)
+        };
+
+        // Peak picking may miss a click within a window of either end
+        let onsets = transcriber.detect_onsets(&samples);
+        assert!(onsets.times.len() >= clicks.len() - 2);
+        for &onset in onsets.times.iter() {
+            assert!(nearest(onset) < 0.02, "onset at {}", onset);
+        }
+
+        let (tempo, _) = transcriber.estimate_tempo(&samples);
+        assert!((tempo - 120.0).abs() < 2.0, "tempo {}", tempo);
+
+        let beats = beat_grid(&onsets.odf, onsets.timing, tempo);
+        assert!(beats.len() >= clicks.len() - 2);
+        for &beat in beats.iter() {
+            assert!(nearest(beat) < 0.02, "beat at {}", beat);
+        }
+    }
+    #[test]
+    fn short_and_silent_recordings_transcribe_to_nothing() {
+        let mut config = Config::default();
+        config.pitch.detector = DetectorKind::Hps;
+        let transcriber = Transcriber::new(config).unwrap();
+        for samples in [vec![0.0; 100], vec![0.0; 44100]] {
+            let samples = Samples::mono(samples, 44100);
+            let transcription = transcriber.transcribe(&samples);
+            assert!(transcription.notes.is_empty());
+            assert!(transcription.onsets.is_empty());
+            assert_eq!(transcription.tempo, 0.0);
+            assert!(transcription.diagnostics.odf.iter().all(|v| v.is_finite()));
+        }
+    }
+}
@@ -17,9 +17,20 @@ pub struct Samples {
 }
 
 impl Samples {
-    fn new(samples: Vec<f32>, spec: SampleSpec) -> Self {
+    pub fn new(samples: Vec<f32>, spec: SampleSpec) -> Self {
         Samples { samples, spec }
     }
+
+    /// Single-channel 32-bit float samples, e.g. generated ones
+    pub fn mono(samples: Vec<f32>, sample_rate: u32) -> Self {
+        let spec = SampleSpec {
+            sample_rate,
+            channels: 1,
+            bits_per_sample: 32,
+            duration_milis: samples.len() as f32 / sample_rate as f32 * 1000.0,
+        };
+        Samples::new(samples, spec)
+    }
 }
 
 impl Deref for Samples {
@@ -30,30 +41,28 @@ impl Deref for Samples {
     }
 }
 
-pub fn file_to_samples(path: &Path) -> Samples {
-    // TODO: pass a path to this function
-
+/// Reads the first channel of a WAV file, float or integer PCM, as samples in [-1, 1]
+pub fn file_to_samples(path: &Path) -> Result<Samples, hound::Error> {
     // Open the WAV file
-    let mut reader = hound::WavReader::open(path).expect("Failed to open WAV file");
-    println!("Sample spec: {:?}", reader.spec());
+    let mut reader = hound::WavReader::open(path)?;
 
     // Collect samples into a vector
-    let mut samples: Vec<f32> = reader
-        .samples::<f32>()
-        .map(|s| s.expect("Failed to read sample"))
-        .collect();
-
-    // let mut samples: Vec<i16> = reader
-    //     .samples::<i16>()
-    //     .map(|s| s.expect("Failed to read sample"))
-    //     .collect();
-
-    samples = samples.into_iter().step_by(2).collect();
-
-    // let clnd: Vec<f32> = samples.clone().iter().map(|f| *f as f32).collect();
-    // plot(&clnd, "Samples.png").unwrap();
+    let mut samples: Vec<f32> = match reader.spec().sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (reader.spec().bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 * scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
 
-    println!("Loaded {} samples", samples.len());
+    // Keep the first channel
+    samples = samples
+        .into_iter()
+        .step_by(reader.spec().channels.max(1) as usize)
+        .collect();
 
     let spec = SampleSpec {
         sample_rate: reader.spec().sample_rate,
@@ -62,7 +71,20 @@ pub fn file_to_samples(path: &Path) -> Samples {
         duration_milis: samples.len() as f32 / reader.spec().sample_rate as f32 * 1000.0,
     };
 
-    print!("Duration: {} s\n", spec.duration_milis / 1000.0);
+    Ok(Samples::new(samples, spec))
+}
 
-    Samples::new(samples.iter().map(|&sample| sample as f32).collect(), spec)
+/// Writes `samples` as a single-channel 32-bit float WAV file
+pub fn samples_to_file(samples: &Samples, path: &Path) -> Result<(), hound::Error> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: samples.spec.sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for sample in samples.iter() {
+        writer.write_sample(*sample)?;
+    }
+    writer.finalize()
 }
@@ -0,0 +1,268 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    key::{Key, Mode},
+    notes::{Note, NoteName},
+    spelling::parse_spelling,
+};
+
+/// Interval patterns of the built-in scales
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScaleKind {
+    Major,
+    NaturalMinor,
+    HarmonicMinor,
+    MelodicMinor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+    Chromatic,
+}
+
+impl ScaleKind {
+    pub const ALL: [ScaleKind; 13] = [
+        ScaleKind::Major,
+        ScaleKind::NaturalMinor,
+        ScaleKind::HarmonicMinor,
+        ScaleKind::MelodicMinor,
+        ScaleKind::Dorian,
+        ScaleKind::Phrygian,
+        ScaleKind::Lydian,
+        ScaleKind::Mixolydian,
+        ScaleKind::Locrian,
+        ScaleKind::MajorPentatonic,
+        ScaleKind::MinorPentatonic,
+        ScaleKind::Blues,
+        ScaleKind::Chromatic,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScaleKind::Major => "major",
+            ScaleKind::NaturalMinor => "minor",
+            ScaleKind::HarmonicMinor => "harmonic-minor",
+            ScaleKind::MelodicMinor => "melodic-minor",
+            ScaleKind::Dorian => "dorian",
+            ScaleKind::Phrygian => "phrygian",
+            ScaleKind::Lydian => "lydian",
+            ScaleKind::Mixolydian => "mixolydian",
+            ScaleKind::Locrian => "locrian",
+            ScaleKind::MajorPentatonic => "major-pentatonic",
+            ScaleKind::MinorPentatonic => "minor-pentatonic",
+            ScaleKind::Blues => "blues",
+            ScaleKind::Chromatic => "chromatic",
+        }
+    }
+
+    /// Semitones above the tonic of every degree
+    pub fn intervals(&self) -> &'static [u8] {
+        match self {
+            ScaleKind::Major => &[0, 2, 4, 5, 7, 9, 11],
+            ScaleKind::NaturalMinor => &[0, 2, 3, 5, 7, 8, 10],
+            ScaleKind::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            ScaleKind::MelodicMinor => &[0, 2, 3, 5, 7, 9, 11],
+            ScaleKind::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            ScaleKind::Phrygian => &[0, 1, 3, 5, 7, 8, 10],
+            ScaleKind::Lydian => &[0, 2, 4, 6, 7, 9, 11],
+            ScaleKind::Mixolydian => &[0, 2, 4, 5, 7, 9, 10],
+            ScaleKind::Locrian => &[0, 1, 3, 5, 6, 8, 10],
+            ScaleKind::MajorPentatonic => &[0, 2, 4, 7, 9],
+            ScaleKind::MinorPentatonic => &[0, 3, 5, 7, 10],
+            ScaleKind::Blues => &[0, 3, 5, 6, 7, 10],
+            ScaleKind::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+        }
+    }
+}
+
+impl fmt::Display for ScaleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for ScaleKind {
+    type Err = ParseScaleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase().replace([' ', '_'], "-");
+        let name = match name.as_str() {
+            "natural-minor" | "aeolian" => "minor",
+            "ionian" => "major",
+            name => name,
+        };
+        ScaleKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+            .ok_or_else(|| ParseScaleError(s.to_string()))
+    }
+}
+
+/// A set of pitch classes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Scale {
+    /// Bit `i` is set if the pitch class `i` semitones above C belongs to the scale
+    pitch_classes: u16,
+}
+
+impl Scale {
+    pub fn new(tonic: NoteName, kind: ScaleKind) -> Self {
+        Scale::from_intervals(tonic, kind.intervals())
+    }
+
+    /// Scale made of the degrees `intervals` semitones above `tonic`
+    pub fn from_intervals(tonic: NoteName, intervals: &[u8]) -> Self {
+        Scale::custom(
+            intervals
+                .iter()
+                .map(|i| NoteName::from_pitch_class(tonic.pitch_class() + i % 12)),
+        )
+    }
+
+    /// Scale made of an arbitrary set of pitch classes
+    pub fn custom(names: impl IntoIterator<Item = NoteName>) -> Self {
+        Scale {
+            pitch_classes: names
+                .into_iter()
+                .fold(0, |mask, name| mask | 1 << name.pitch_class()),
+        }
+    }
+
+    /// A scale without any pitch class, which constrains nothing
+    pub fn is_empty(&self) -> bool {
+        self.pitch_classes == 0
+    }
+
+    pub fn contains(&self, name: NoteName) -> bool {
+        self.pitch_classes & (1 << name.pitch_class()) != 0
+    }
+
+    /// Pitch classes of the scale in ascending order from C
+    pub fn names(&self) -> Vec<NoteName> {
+        (0..12)
+            .map(NoteName::from_pitch_class)
+            .filter(|name| self.contains(*name))
+            .collect()
+    }
+}
+
+impl From<Key> for Scale {
+    fn from(key: Key) -> Self {
+        match key.mode {
+            Mode::Major => Scale::new(key.tonic, ScaleKind::Major),
+            Mode::Minor => Scale::new(key.tonic, ScaleKind::NaturalMinor),
+        }
+    }
+}
+
+/// A built-in scale on a tonic, written as e.g. "D dorian" or "A minor-pentatonic"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NamedScale {
+    pub tonic: NoteName,
+    pub kind: ScaleKind,
+}
+
+impl NamedScale {
+    pub fn new(tonic: NoteName, kind: ScaleKind) -> Self {
+        NamedScale { tonic, kind }
+    }
+}
+
+impl From<NamedScale> for Scale {
+    fn from(scale: NamedScale) -> Self {
+        Scale::new(scale.tonic, scale.kind)
+    }
+}
+
+impl fmt::Display for NamedScale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.tonic, self.kind)
+    }
+}
+
+impl FromStr for NamedScale {
+    type Err = ParseScaleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseScaleError(s.to_string());
+        let (spelling, rest) = parse_spelling(s.trim()).ok_or_else(err)?;
+        let kind = rest.parse().map_err(|_| err())?;
+        Ok(NamedScale::new(spelling.name(), kind))
+    }
+}
+
+impl TryFrom<String> for NamedScale {
+    type Error = ParseScaleError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<NamedScale> for String {
+    fn from(scale: NamedScale) -> Self {
+        scale.to_string()
+    }
+}
+
+/// Text that is not a tonic followed by a built-in scale name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseScaleError(pub String);
+
+impl fmt::Display for ParseScaleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = ScaleKind::ALL.iter().map(|k| k.name()).collect();
+        write!(
+            f,
+            "invalid scale {:?}, expected a tonic and one of: {}",
+            self.0,
+            names.join(", ")
+        )
+    }
+}
+
+impl Error for ParseScaleError {}
+
+/// Restricts note mapping to a scale. A note outside the scale is only picked when the
+/// pitch is more than `penalty` cents closer to it than to the nearest scale note.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScaleConstraint {
+    pub scale: Scale,
+    /// Cost in cents of choosing a note outside the scale. `f32::INFINITY` snaps
+    /// every pitch to the scale.
+    pub penalty: f32,
+}
+
+impl ScaleConstraint {
+    pub fn new(scale: Scale, penalty: f32) -> Self {
+        ScaleConstraint { scale, penalty }
+    }
+
+    /// Constraint that always snaps to the scale
+    pub fn strict(scale: Scale) -> Self {
+        ScaleConstraint::new(scale, f32::INFINITY)
+    }
+
+    /// Cheapest of `candidates` for a pitch of `freq` Hz
+    pub fn choose(&self, freq: f32, candidates: impl IntoIterator<Item = Note>) -> Option<Note> {
+        candidates
+            .into_iter()
+            .map(|note| {
+                let penalty = if self.scale.contains(note.name) {
+                    0.0
+                } else {
+                    self.penalty
+                };
+                (note, note.cents(freq).abs() + penalty)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(note, _)| note)
+    }
+}
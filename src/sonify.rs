@@ -0,0 +1,106 @@
+use std::f32::consts::PI;
+
+use crate::{
+    algorithms::pitch::PitchTrack,
+    notes::NoteEvent,
+    samples::Samples,
+    synth::{Synth, Waveform},
+};
+
+/// Frequency of the onset clicks in Hz
+const CLICK_FREQ: f32 = 2000.0;
+
+/// Seconds for a click to decay by 60 dB
+const CLICK_DECAY: f32 = 0.02;
+
+/// Renders transcriptions back to audio, to check them by ear against the recording.
+#[derive(Debug, Clone)]
+pub struct Sonifier {
+    pub synth: Synth,
+    /// Level of the original recording in the mix, in [0, 1]. The resynthesis gets the
+    /// rest.
+    pub balance: f32,
+    /// Peak amplitude of the clicks marking onsets, 0 for none
+    pub click_level: f32,
+}
+
+impl Default for Sonifier {
+    fn default() -> Self {
+        Sonifier {
+            synth: Synth {
+                amplitude: 0.8,
+                ..Synth::new(Waveform::sawtooth(6))
+            },
+            balance: 0.0,
+            click_level: 0.0,
+        }
+    }
+}
+
+impl Sonifier {
+    pub fn new(balance: f32, click_level: f32) -> Self {
+        Sonifier {
+            balance,
+            click_level,
+            ..Default::default()
+        }
+    }
+
+    /// `notes` mixed with `original`, with clicks at `onsets` (in seconds)
+    pub fn notes(&self, original: &Samples, notes: &[NoteEvent], onsets: &[f32]) -> Samples {
+        let synthesized = self.synth_for(original).render(notes);
+        self.mix(original, &synthesized, onsets)
+    }
+
+    /// The f0 of `track` mixed with `original`, with clicks at `onsets` (in seconds)
+    pub fn pitch_track(
+        &self,
+        original: &Samples,
+        track: &PitchTrack,
+        min_voiced_prob: f32,
+        onsets: &[f32],
+    ) -> Samples {
+        let synthesized = self
+            .synth_for(original)
+            .render_track(track, min_voiced_prob);
+        self.mix(original, &synthesized, onsets)
+    }
+
+    /// Weighted sum of `original` and `synthesized`, plus the clicks, scaled down if it
+    /// would clip
+    pub fn mix(&self, original: &Samples, synthesized: &Samples, onsets: &[f32]) -> Samples {
+        let sr = original.spec.sample_rate as f32;
+        let balance = self.balance.clamp(0.0, 1.0);
+        let mut mix = vec![0.0; original.len().max(synthesized.len())];
+        for (i, sample) in mix.iter_mut().enumerate() {
+            let a = original.get(i).copied().unwrap_or(0.0);
+            let b = synthesized.get(i).copied().unwrap_or(0.0);
+            *sample = balance * a + (1.0 - balance) * b;
+        }
+
+        if self.click_level > 0.0 {
+            let length = (CLICK_DECAY * sr) as usize;
+            for onset in onsets.iter() {
+                let start = (onset * sr).round() as usize;
+                for (i, sample) in mix.iter_mut().skip(start).take(length).enumerate() {
+                    let time = i as f32 / sr;
+                    let decay = 10.0f32.powf(-3.0 * time / CLICK_DECAY);
+                    *sample += self.click_level * decay * (2.0 * PI * CLICK_FREQ * time).sin();
+                }
+            }
+        }
+
+        let peak = mix.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        if peak > 1.0 {
+            mix.iter_mut().for_each(|s| *s /= peak);
+        }
+        Samples::mono(mix, original.spec.sample_rate)
+    }
+
+    fn synth_for(&self, original: &Samples) -> Synth {
+        Synth {
+            sample_rate: original.spec.sample_rate,
+            ..self.synth.clone()
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::notes::{Note, NoteName};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Letter {
+    C,
+    D,
+    E,
+    F,
+    G,
+    A,
+    B,
+}
+
+impl Letter {
+    /// Semitones above C of the natural note
+    pub fn pitch_class(&self) -> u8 {
+        match self {
+            Letter::C => 0,
+            Letter::D => 2,
+            Letter::E => 4,
+            Letter::F => 5,
+            Letter::G => 7,
+            Letter::A => 9,
+            Letter::B => 11,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Accidental {
+    DoubleFlat,
+    Flat,
+    Natural,
+    Sharp,
+    DoubleSharp,
+}
+
+impl Accidental {
+    /// Semitones the accidental raises (or lowers) the natural note
+    pub fn offset(&self) -> i32 {
+        match self {
+            Accidental::DoubleFlat => -2,
+            Accidental::Flat => -1,
+            Accidental::Natural => 0,
+            Accidental::Sharp => 1,
+            Accidental::DoubleSharp => 2,
+        }
+    }
+
+    fn from_offset(offset: i32) -> Option<Self> {
+        match offset {
+            -2 => Some(Accidental::DoubleFlat),
+            -1 => Some(Accidental::Flat),
+            0 => Some(Accidental::Natural),
+            1 => Some(Accidental::Sharp),
+            2 => Some(Accidental::DoubleSharp),
+            _ => None,
+        }
+    }
+}
+
+/// Written name of a pitch class, e.g. C# or Db for the same [`NoteName`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Spelling {
+    pub letter: Letter,
+    pub accidental: Accidental,
+}
+
+impl Spelling {
+    pub fn new(letter: Letter, accidental: Accidental) -> Self {
+        Spelling { letter, accidental }
+    }
+
+    /// Spelling at `position` on the line of fifths, where C = 0, G = 1 and F = -1.
+    /// `None` beyond double sharps and double flats.
+    pub fn from_fifths(position: i32) -> Option<Self> {
+        const LETTERS: [Letter; 7] = [
+            Letter::F,
+            Letter::C,
+            Letter::G,
+            Letter::D,
+            Letter::A,
+            Letter::E,
+            Letter::B,
+        ];
+        let letter = LETTERS[(position + 1).rem_euclid(7) as usize];
+        let accidental = Accidental::from_offset((position + 1).div_euclid(7))?;
+        Some(Spelling::new(letter, accidental))
+    }
+
+    /// Semitones above C, possibly outside 0..12 (e.g. -1 for Cb)
+    pub fn offset(&self) -> i32 {
+        self.letter.pitch_class() as i32 + self.accidental.offset()
+    }
+
+    pub fn name(&self) -> NoteName {
+        NoteName::from_pitch_class(self.offset().rem_euclid(12) as u8)
+    }
+
+    /// Octave number of `note` when written with this spelling, e.g. 4 for B3 spelled
+    /// as Cb4
+    pub fn octave(&self, note: &Note) -> i32 {
+        (note.semitones() - self.offset()).div_euclid(12)
+    }
+}
+
+impl NoteName {
+    /// Spelling with a sharp for black keys
+    pub fn sharp(&self) -> Spelling {
+        Spelling::from_fifths(fifths_position(*self, -1)).unwrap()
+    }
+
+    /// Spelling with a flat for black keys
+    pub fn flat(&self) -> Spelling {
+        Spelling::from_fifths(fifths_position(*self, -6)).unwrap()
+    }
+}
+
+/// Position on the line of fifths of the spelling of `name` within `low..low + 12`
+pub(crate) fn fifths_position(name: NoteName, low: i32) -> i32 {
+    // 7 is its own inverse modulo 12, so this is a position of the pitch class
+    let position = name.pitch_class() as i32 * 7;
+    low + (position - low).rem_euclid(12)
+}
+
+impl fmt::Display for Letter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl fmt::Display for Accidental {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Accidental::DoubleFlat => "bb",
+            Accidental::Flat => "b",
+            Accidental::Natural => "",
+            Accidental::Sharp => "#",
+            Accidental::DoubleSharp => "##",
+        };
+        f.write_str(symbol)
+    }
+}
+
+impl fmt::Display for Spelling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.letter, self.accidental)
+    }
+}
+
+impl fmt::Display for NoteName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.sharp().fmt(f)
+    }
+}
+
+/// Text that is not a valid note, spelling or key name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseNoteError(pub String);
+
+impl fmt::Display for ParseNoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid note name: {:?}", self.0)
+    }
+}
+
+impl Error for ParseNoteError {}
+
+/// Splits a leading spelling such as "Bb" or "F#" off `s`
+pub(crate) fn parse_spelling(s: &str) -> Option<(Spelling, &str)> {
+    let mut chars = s.char_indices();
+    let letter = match chars.next()?.1.to_ascii_uppercase() {
+        'C' => Letter::C,
+        'D' => Letter::D,
+        'E' => Letter::E,
+        'F' => Letter::F,
+        'G' => Letter::G,
+        'A' => Letter::A,
+        'B' => Letter::B,
+        _ => return None,
+    };
+
+    let mut offset = 0;
+    let mut rest = &s[1..];
+    for (i, c) in chars {
+        match c {
+            '#' | '♯' => offset += 1,
+            'x' | '𝄪' => offset += 2,
+            'b' | '♭' => offset -= 1,
+            '♮' => {}
+            _ => {
+                rest = &s[i..];
+                break;
+            }
+        }
+        rest = &s[i + c.len_utf8()..];
+    }
+
+    Some((
+        Spelling::new(letter, Accidental::from_offset(offset)?),
+        rest,
+    ))
+}
+
+impl FromStr for Spelling {
+    type Err = ParseNoteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_spelling(s.trim()) {
+            Some((spelling, "")) => Ok(spelling),
+            _ => Err(ParseNoteError(s.to_string())),
+        }
+    }
+}
+
+impl FromStr for NoteName {
+    type Err = ParseNoteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<Spelling>().map(|spelling| spelling.name())
+    }
+}
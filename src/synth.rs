@@ -0,0 +1,408 @@
+use std::f32::consts::PI;
+
+use crate::{
+    algorithms::{pitch::PitchTrack, shared::XorShift},
+    notes::NoteEvent,
+    samples::Samples,
+};
+
+/// Delays in seconds of the parallel comb filters of the reverb
+const COMB_DELAYS: [f32; 4] = [0.0297, 0.0371, 0.0411, 0.0437];
+
+/// Seconds to fade the oscillator in or out when rendering a pitch track
+const TRACK_FADE: f32 = 0.005;
+
+/// Spectrum of the oscillator
+#[derive(Debug, Clone, PartialEq)]
+pub enum Waveform {
+    Sine,
+    /// Amplitudes of the partials, starting at the fundamental
+    Harmonic(Vec<f32>),
+}
+
+impl Waveform {
+    /// Partials with amplitudes 1/k, as a band-limited sawtooth
+    pub fn sawtooth(partials: usize) -> Self {
+        Waveform::Harmonic((1..=partials).map(|k| 1.0 / k as f32).collect())
+    }
+
+    /// Odd partials with amplitudes 1/k, as a band-limited square wave
+    pub fn square(partials: usize) -> Self {
+        Waveform::Harmonic(
+            (1..=partials)
+                .map(|k| if k % 2 == 1 { 1.0 / k as f32 } else { 0.0 })
+                .collect(),
+        )
+    }
+
+    fn partials(&self) -> &[f32] {
+        match self {
+            Waveform::Sine => &[1.0],
+            Waveform::Harmonic(amplitudes) => amplitudes,
+        }
+    }
+}
+
+/// Attack, decay, sustain, release envelope. The release starts at the offset of the
+/// note, so the note rings for `release` seconds after it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Envelope {
+    /// Seconds from silence to full level
+    pub attack: f32,
+    /// Seconds from full level to the sustain level
+    pub decay: f32,
+    /// Level in [0, 1] held until the offset
+    pub sustain: f32,
+    /// Seconds from the sustain level to silence
+    pub release: f32,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Envelope {
+            attack: 0.01,
+            decay: 0.1,
+            sustain: 0.7,
+            release: 0.05,
+        }
+    }
+}
+
+impl Envelope {
+    /// Gain at `time` seconds after the onset of a note lasting `duration` seconds
+    pub fn gain(&self, time: f32, duration: f32) -> f32 {
+        if time < 0.0 {
+            return 0.0;
+        }
+        if time >= duration {
+            let release = (time - duration) / self.release.max(f32::EPSILON);
+            return self.level(duration) * (1.0 - release).max(0.0);
+        }
+        self.level(time)
+    }
+
+    /// Gain while the note is held
+    fn level(&self, time: f32) -> f32 {
+        if time < self.attack {
+            time / self.attack
+        } else if time < self.attack + self.decay {
+            1.0 - (1.0 - self.sustain) * (time - self.attack) / self.decay
+        } else {
+            self.sustain
+        }
+    }
+}
+
+/// Periodic frequency modulation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vibrato {
+    /// Modulation frequency in Hz
+    pub rate: f32,
+    /// Peak deviation in cents
+    pub depth: f32,
+}
+
+impl Vibrato {
+    /// Frequency ratio at `time` seconds
+    pub fn ratio(&self, time: f32) -> f32 {
+        2.0f32.powf(self.depth / 1200.0 * (2.0 * PI * self.rate * time).sin())
+    }
+}
+
+/// Schroeder reverb made of parallel feedback comb filters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reverb {
+    /// Seconds for the tail to decay by 60 dB
+    pub decay: f32,
+    /// Level of the reverberated signal, in [0, 1]
+    pub mix: f32,
+}
+
+/// Renders note lists to audio with known ground truth, to test the analyses on
+/// controlled signals. The output is deterministic for a given `seed`.
+#[derive(Debug, Clone)]
+pub struct Synth {
+    pub sample_rate: u32,
+    pub waveform: Waveform,
+    pub envelope: Envelope,
+    pub vibrato: Option<Vibrato>,
+    /// Peak amplitude of every note
+    pub amplitude: f32,
+    /// Amplitude of the white noise added to the whole signal
+    pub noise: f32,
+    pub reverb: Option<Reverb>,
+    pub seed: u64,
+}
+
+impl Default for Synth {
+    fn default() -> Self {
+        Synth {
+            sample_rate: 44100,
+            waveform: Waveform::Sine,
+            envelope: Envelope::default(),
+            vibrato: None,
+            amplitude: 0.5,
+            noise: 0.0,
+            reverb: None,
+            seed: 0,
+        }
+    }
+}
+
+impl Synth {
+    pub fn new(waveform: Waveform) -> Self {
+        Synth {
+            waveform,
+            ..Default::default()
+        }
+    }
+
+    /// Renders `notes` at the frequency of their note, until the end of the last
+    /// release and reverb tail
+    pub fn render(&self, notes: &[NoteEvent]) -> Samples {
+        let sr = self.sample_rate as f32;
+        let tail = self.envelope.release + self.reverb.map_or(0.0, |r| r.decay);
+        let end = notes.iter().fold(0.0f32, |end, n| end.max(n.offset)) + tail;
+        let mut samples = vec![0.0; (end * sr).ceil() as usize];
+
+        for note in notes.iter() {
+            self.add_note(&mut samples, note.onset, note.duration(), note.note.freq);
+        }
+        self.finish(samples)
+    }
+
+    /// Adds one note to `samples`
+    pub fn add_note(&self, samples: &mut [f32], onset: f32, duration: f32, freq: f32) {
+        let sr = self.sample_rate as f32;
+        let start = (onset * sr).round() as usize;
+        let length = ((duration + self.envelope.release) * sr).ceil() as usize;
+
+        let mut phase = 0.0f32;
+        for (i, sample) in samples.iter_mut().skip(start).take(length).enumerate() {
+            let time = i as f32 / sr;
+            let freq = freq * self.vibrato.map_or(1.0, |v| v.ratio(time));
+            phase = (phase + 2.0 * PI * freq / sr) % (2.0 * PI);
+            *sample += self.envelope.gain(time, duration) * self.oscillator(phase, freq);
+        }
+    }
+
+    /// Renders the f0 of `track` with a continuous oscillator, interpolated between
+    /// frames and faded in and out around unvoiced frames. Envelope and vibrato are not
+    /// applied.
+    pub fn render_track(&self, track: &PitchTrack, min_voiced_prob: f32) -> Samples {
+        let sr = self.sample_rate as f32;
+        let f0 = track.f0(min_voiced_prob);
+        let first = track.frames.first().map_or(0.0, |f| f.time);
+        let end = track.frames.last().map_or(0.0, |f| f.time) + 1.0 / track.frame_rate();
+        let mut samples = vec![0.0; (end * sr).ceil() as usize];
+        let fade_step = 1.0 / (TRACK_FADE * sr);
+
+        let (mut phase, mut gain, mut freq) = (0.0f32, 0.0f32, 0.0f32);
+        for (i, sample) in samples.iter_mut().enumerate() {
+            let position = ((i as f32 / sr - first) * track.frame_rate()).max(0.0);
+            let frame = (position as usize).min(f0.len().saturating_sub(1));
+            let current = match (f0.get(frame).copied().flatten(), f0.get(frame + 1)) {
+                (Some(a), Some(Some(b))) => Some(a + (b - a) * position.fract()),
+                (current, _) => current,
+            };
+
+            if let Some(current) = current {
+                freq = current;
+                gain = (gain + fade_step).min(1.0);
+            } else {
+                gain = (gain - fade_step).max(0.0);
+            }
+            if gain > 0.0 {
+                phase = (phase + 2.0 * PI * freq / sr) % (2.0 * PI);
+                *sample = gain * self.oscillator(phase, freq);
+            }
+        }
+        self.finish(samples)
+    }
+
+    /// Value of the waveform at `phase`, scaled to `amplitude`. Partials above the
+    /// Nyquist frequency are dropped.
+    fn oscillator(&self, phase: f32, freq: f32) -> f32 {
+        let sr = self.sample_rate as f32;
+        let partials = self.waveform.partials();
+        let norm: f32 = partials
+            .iter()
+            .map(|a| a.abs())
+            .sum::<f32>()
+            .max(f32::EPSILON);
+        let value: f32 = partials
+            .iter()
+            .enumerate()
+            .filter(|(k, _)| (*k + 1) as f32 * freq < sr / 2.0)
+            .map(|(k, a)| a * ((k + 1) as f32 * phase).sin())
+            .sum();
+        self.amplitude * value / norm
+    }
+
+    /// Applies the reverb and the noise
+    fn finish(&self, mut samples: Vec<f32>) -> Samples {
+        if let Some(reverb) = self.reverb {
+            samples = self.reverberate(&samples, reverb);
+        }
+        if self.noise > 0.0 {
+            let mut rng = XorShift::new(self.seed);
+            for sample in samples.iter_mut() {
+                *sample += self.noise * rng.next_signed();
+            }
+        }
+        Samples::mono(samples, self.sample_rate)
+    }
+
+    fn reverberate(&self, dry: &[f32], reverb: Reverb) -> Vec<f32> {
+        let sr = self.sample_rate as f32;
+        let mut wet = vec![0.0; dry.len()];
+        for delay in COMB_DELAYS {
+            let lag = (delay * sr) as usize;
+            // Feedback so that the loop loses 60 dB in `decay` seconds
+            let feedback = 10.0f32.powf(-3.0 * delay / reverb.decay.max(f32::EPSILON));
+            let mut comb = vec![0.0; dry.len()];
+            for i in 0..dry.len() {
+                let echo = if i >= lag { comb[i - lag] } else { 0.0 };
+                comb[i] = dry[i] + feedback * echo;
+                wet[i] += comb[i] / COMB_DELAYS.len() as f32;
+            }
+        }
+        dry.iter()
+            .zip(wet.iter())
+            .map(|(d, w)| (1.0 - reverb.mix) * d + reverb.mix * w)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notes::Note;
+
+    fn a4(onset: f32, offset: f32) -> Vec<NoteEvent> {
+        vec![NoteEvent::new(onset, offset, "A4".parse::<Note>().unwrap())]
+    }
+
+    /// Samples from `from` to `to` seconds
+    fn window(samples: &Samples, from: f32, to: f32) -> &[f32] {
+        let sr = samples.spec.sample_rate as f32;
+        let end = ((to * sr) as usize).min(samples.len());
+        &samples[(from * sr) as usize..end]
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    /// Frequency of every period between upward zero crossings, located to a fraction
+    /// of a sample
+    fn periods(samples: &[f32], sample_rate: u32) -> Vec<f32> {
+        let crossings: Vec<f32> = samples
+            .windows(2)
+            .enumerate()
+            .filter(|(_, w)| w[0] < 0.0 && w[1] >= 0.0)
+            .map(|(i, w)| i as f32 + w[0] / (w[0] - w[1]))
+            .collect();
+        crossings
+            .windows(2)
+            .map(|c| sample_rate as f32 / (c[1] - c[0]))
+            .collect()
+    }
+
+    #[test]
+    fn rendering_lasts_until_the_end_of_the_tails() {
+        let mut synth = Synth::default();
+        let sr = synth.sample_rate as f32;
+        let notes = [a4(0.5, 0.8), a4(0.2, 1.0)].concat();
+        assert!((synth.render(&notes).len() as f32 / sr - 1.05).abs() <= 1.0 / sr);
+
+        synth.reverb = Some(Reverb {
+            decay: 0.3,
+            mix: 0.5,
+        });
+        assert!((synth.render(&notes).len() as f32 / sr - 1.35).abs() <= 1.0 / sr);
+        assert!(!synth.render(&[]).is_empty());
+    }
+
+    #[test]
+    fn envelope_shapes_the_note() {
+        let synth = Synth {
+            envelope: Envelope {
+                attack: 0.01,
+                decay: 0.1,
+                sustain: 0.5,
+                release: 0.05,
+            },
+            ..Synth::default()
+        };
+        let samples = synth.render(&a4(0.1, 0.6));
+
+        assert_eq!(peak(window(&samples, 0.0, 0.1)), 0.0);
+        assert!(peak(window(&samples, 0.1, 0.101)) < 0.05);
+        // Full level at the end of the attack, then the sustain level
+        assert!((peak(window(&samples, 0.108, 0.113)) - 0.5).abs() < 0.05);
+        assert!((peak(window(&samples, 0.3, 0.6)) - 0.25).abs() < 0.01);
+        // Half way through the release
+        assert!((peak(window(&samples, 0.624, 0.626)) - 0.125).abs() < 0.02);
+        assert!(peak(window(&samples, 0.645, 0.65)) < 0.03);
+    }
+
+    #[test]
+    fn vibrato_deviates_by_its_depth() {
+        let mut synth = Synth::default();
+        let notes = a4(0.0, 1.0);
+        let steady = periods(window(&synth.render(&notes), 0.1, 0.9), synth.sample_rate);
+        assert!(steady.iter().all(|f| (f - 440.0).abs() < 0.5));
+
+        synth.vibrato = Some(Vibrato {
+            rate: 5.0,
+            depth: 50.0,
+        });
+        let vibrato = periods(window(&synth.render(&notes), 0.1, 0.9), synth.sample_rate);
+        let highest = vibrato.iter().copied().fold(f32::MIN, f32::max);
+        let lowest = vibrato.iter().copied().fold(f32::MAX, f32::min);
+        // 50 cents either side of A4: 452.9 and 427.4 Hz
+        assert!((highest - 452.9).abs() < 2.0, "highest {}", highest);
+        assert!((lowest - 427.4).abs() < 2.0, "lowest {}", lowest);
+    }
+
+    #[test]
+    fn reverb_rings_after_the_release() {
+        let mut synth = Synth::default();
+        let notes = a4(0.1, 0.5);
+        let dry = synth.render(&notes);
+        assert_eq!(peak(window(&dry, 0.55, 1.0)), 0.0);
+
+        synth.reverb = Some(Reverb {
+            decay: 0.5,
+            mix: 0.5,
+        });
+        let wet = synth.render(&notes);
+        let early = rms(window(&wet, 0.55, 0.65));
+        let late = rms(window(&wet, 0.85, 0.95));
+        let end = rms(window(&wet, 1.0, 1.05));
+        assert!(early > late && late > end && end > 0.0);
+        // Close to 60 dB down by the end of the decay
+        assert!(end < 0.01 * early, "{} after {}", end, early);
+    }
+
+    #[test]
+    fn noise_is_determined_by_the_seed() {
+        let synth = |seed| Synth {
+            noise: 0.1,
+            seed,
+            ..Synth::default()
+        };
+        let notes = a4(0.0, 0.2);
+        let noisy = synth(1).render(&notes);
+        assert_eq!(*noisy, *synth(1).render(&notes));
+        assert_ne!(*noisy, *synth(2).render(&notes));
+
+        let silent = synth(1).render(&[]);
+        assert!(peak(&silent) <= 0.1 && rms(&silent) > 0.03);
+    }
+}
@@ -0,0 +1,406 @@
+use std::{error::Error, fmt, fs, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::notes::NOTE_NAMES;
+
+/// Width of a pure fifth (3/2) in cents
+const PURE_FIFTH: f32 = 701.955;
+
+/// Syntonic comma (81/80) in cents
+const SYNTONIC_COMMA: f32 = 21.506;
+
+/// A tuning system: the pitches of one period (usually an octave), repeated over the
+/// whole range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Temperament {
+    pub name: String,
+    /// Cents above the first degree of every degree in the period, starting at 0
+    pub degrees: Vec<f32>,
+    /// Width of the period in cents
+    pub period: f32,
+    /// Name of every degree
+    pub degree_names: Vec<String>,
+}
+
+/// A frequency mapped to the nearest degree of a temperament
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScaleDegree {
+    /// Index into the degrees of the temperament
+    pub degree: usize,
+    /// Periods above (or below, if negative) the reference
+    pub period: i32,
+    pub name: String,
+    /// Nominal frequency of the degree in Hz
+    pub freq: f32,
+    /// Signed deviation of the mapped frequency from the degree, in cents
+    pub cents: f32,
+}
+
+impl Temperament {
+    /// Temperament with the given degrees in cents and generic degree names
+    pub fn new(name: &str, degrees: Vec<f32>, period: f32) -> Self {
+        let degree_names = (0..degrees.len()).map(|i| i.to_string()).collect();
+        Temperament {
+            name: name.to_string(),
+            degrees,
+            period,
+            degree_names,
+        }
+    }
+
+    /// Temperament with degrees given as frequency ratios to the first one, and a
+    /// period of an octave
+    pub fn from_ratios(name: &str, ratios: &[f32]) -> Self {
+        Temperament::new(
+            name,
+            ratios.iter().map(|r| 1200.0 * r.log2()).collect(),
+            1200.0,
+        )
+    }
+
+    /// Equal division of the octave in `divisions` steps. 12 and 24 steps get note
+    /// names starting at C, with "+" marking a quarter tone up. `None` for 0 steps.
+    pub fn equal(divisions: usize) -> Option<Self> {
+        if divisions == 0 {
+            return None;
+        }
+        let step = 1200.0 / divisions as f32;
+        let mut temperament = Temperament::new(
+            &format!("{}-TET", divisions),
+            (0..divisions).map(|i| i as f32 * step).collect(),
+            1200.0,
+        );
+        match divisions {
+            12 => temperament.degree_names = chromatic_names(),
+            24 => {
+                temperament.degree_names = chromatic_names()
+                    .into_iter()
+                    .flat_map(|name| [name.clone(), format!("{}+", name)])
+                    .collect()
+            }
+            _ => {}
+        }
+        Some(temperament)
+    }
+
+    /// 5-limit just intonation, starting at C
+    pub fn just_intonation() -> Self {
+        let ratios = [
+            1.0,
+            16.0 / 15.0,
+            9.0 / 8.0,
+            6.0 / 5.0,
+            5.0 / 4.0,
+            4.0 / 3.0,
+            45.0 / 32.0,
+            3.0 / 2.0,
+            8.0 / 5.0,
+            5.0 / 3.0,
+            9.0 / 5.0,
+            15.0 / 8.0,
+        ];
+        Temperament {
+            degree_names: chromatic_names(),
+            ..Temperament::from_ratios("Just intonation", &ratios)
+        }
+    }
+
+    /// Pythagorean tuning: a chain of pure fifths from Db to F#
+    pub fn pythagorean() -> Self {
+        Temperament::chain_of_fifths("Pythagorean", PURE_FIFTH, -5)
+    }
+
+    /// Quarter-comma meantone: a chain of fifths narrowed by a quarter of the syntonic
+    /// comma, from Eb to G#
+    pub fn meantone() -> Self {
+        Temperament::chain_of_fifths(
+            "Quarter-comma meantone",
+            PURE_FIFTH - SYNTONIC_COMMA / 4.0,
+            -3,
+        )
+    }
+
+    /// Twelve pitch classes generated by stacking `fifth` cents, starting `lowest`
+    /// fifths away from C
+    fn chain_of_fifths(name: &str, fifth: f32, lowest: i32) -> Self {
+        let mut degrees = vec![0.0; 12];
+        for position in lowest..lowest + 12 {
+            let pitch_class = (position * 7).rem_euclid(12) as usize;
+            degrees[pitch_class] = (position as f32 * fifth).rem_euclid(1200.0);
+        }
+        Temperament {
+            name: name.to_string(),
+            degrees,
+            period: 1200.0,
+            degree_names: chromatic_names(),
+        }
+    }
+
+    /// Loads a Scala (.scl) tuning file
+    pub fn from_scl(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(Temperament::parse_scl(&fs::read_to_string(path)?)?)
+    }
+
+    /// Parses the contents of a Scala (.scl) tuning file. The last pitch of the file
+    /// is taken as the period.
+    pub fn parse_scl(text: &str) -> Result<Self, ParseSclError> {
+        let mut lines = text.lines().filter(|l| !l.trim_start().starts_with('!'));
+
+        let description = lines
+            .next()
+            .ok_or_else(|| ParseSclError("missing description".to_string()))?
+            .trim();
+        let count: usize = lines
+            .next()
+            .and_then(|l| l.split_whitespace().next())
+            .and_then(|l| l.parse().ok())
+            .ok_or_else(|| ParseSclError("missing number of notes".to_string()))?;
+
+        let pitches = lines
+            .filter(|l| !l.trim().is_empty())
+            .take(count)
+            .map(parse_scl_pitch)
+            .collect::<Result<Vec<f32>, _>>()?;
+        if pitches.len() != count || count == 0 {
+            return Err(ParseSclError(format!(
+                "expected {} notes, found {}",
+                count,
+                pitches.len()
+            )));
+        }
+
+        let period = pitches[count - 1];
+        let mut degrees = vec![0.0];
+        degrees.extend_from_slice(&pitches[..count - 1]);
+        Ok(Temperament::new(description, degrees, period))
+    }
+
+    /// Deviation in cents of every pitch class from 12-TET, with A at 0, taking the
+    /// first degree as C. `None` unless there are 12 degrees to the octave.
+    pub fn pitch_class_offsets(&self) -> Option<[f32; 12]> {
+        if self.degrees.len() != 12 || (self.period - 1200.0).abs() > 0.01 {
+            return None;
+        }
+        let a = self.degrees[9] - 900.0;
+        let mut offsets = [0.0; 12];
+        for (pitch_class, offset) in offsets.iter_mut().enumerate() {
+            *offset = self.degrees[pitch_class] - 100.0 * pitch_class as f32 - a;
+        }
+        Some(offsets)
+    }
+
+    /// Frequency of `degree`, `period` periods above `reference`, the frequency of the
+    /// first degree
+    pub fn freq(&self, degree: usize, period: i32, reference: f32) -> f32 {
+        let cents = self.degrees[degree] + period as f32 * self.period;
+        reference * 2.0f32.powf(cents / 1200.0)
+    }
+
+    /// Nearest degree to `freq`, given the frequency of the first degree
+    pub fn degree(&self, freq: f32, reference: f32) -> ScaleDegree {
+        let cents = 1200.0 * (freq / reference).log2();
+        let period = (cents / self.period).floor() as i32;
+        let within = cents - period as f32 * self.period;
+
+        // The first degree of the next period competes with the top of this one
+        let (degree, period, deviation) = self
+            .degrees
+            .iter()
+            .enumerate()
+            .map(|(i, d)| (i, period, within - d))
+            .chain(std::iter::once((0, period + 1, within - self.period)))
+            .min_by(|a, b| a.2.abs().total_cmp(&b.2.abs()))
+            .unwrap();
+
+        ScaleDegree {
+            degree,
+            period,
+            name: self.degree_names[degree].clone(),
+            freq: self.freq(degree, period, reference),
+            cents: deviation,
+        }
+    }
+}
+
+/// A 12-note temperament chosen by name or loaded from a Scala file, as written in a
+/// config: "12-tet", "just", "pythagorean", "meantone" or a path ending in ".scl".
+/// Scala files are only read by [`TemperamentSetting::load`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TemperamentSetting {
+    source: String,
+    offsets: Option<[f32; 12]>,
+}
+
+impl TemperamentSetting {
+    pub const NAMES: [&'static str; 4] = ["12-tet", "just", "pythagorean", "meantone"];
+
+    /// Deviation in cents of every pitch class from 12-TET, see
+    /// [`Temperament::pitch_class_offsets`]. `None` for a Scala file that has not
+    /// been loaded yet.
+    pub fn offsets(&self) -> Option<[f32; 12]> {
+        self.offsets
+    }
+
+    /// Reads the Scala file, if the setting names one that has not been read yet,
+    /// resolving a relative path against `dir`
+    pub fn load(&mut self, dir: &Path) -> Result<(), ParseTemperamentError> {
+        if self.offsets.is_some() {
+            return Ok(());
+        }
+        let path = dir.join(&self.source);
+        let temperament = Temperament::from_scl(&path)
+            .map_err(|e| ParseTemperamentError(format!("{}: {}", path.display(), e)))?;
+        let offsets = temperament.pitch_class_offsets().ok_or_else(|| {
+            ParseTemperamentError(format!(
+                "{}: {} degrees to a period of {} cents, expected 12 degrees to the octave",
+                path.display(),
+                temperament.degrees.len(),
+                temperament.period
+            ))
+        })?;
+        self.offsets = Some(offsets);
+        Ok(())
+    }
+}
+
+impl fmt::Display for TemperamentSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl FromStr for TemperamentSetting {
+    type Err = ParseTemperamentError;
+
+    /// Builds the named temperament, or keeps the path of the Scala file to load
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let source = s.trim();
+        let temperament = match source.to_lowercase().as_str() {
+            "12-tet" => Temperament::equal(12).expect("12 steps"),
+            "just" => Temperament::just_intonation(),
+            "pythagorean" => Temperament::pythagorean(),
+            "meantone" => Temperament::meantone(),
+            name if name.ends_with(".scl") => {
+                return Ok(TemperamentSetting {
+                    source: source.to_string(),
+                    offsets: None,
+                });
+            }
+            _ => {
+                return Err(ParseTemperamentError(format!(
+                    "unknown name {:?}, expected one of {} or a .scl file",
+                    source,
+                    TemperamentSetting::NAMES.join(", ")
+                )));
+            }
+        };
+        Ok(TemperamentSetting {
+            source: source.to_string(),
+            offsets: temperament.pitch_class_offsets(),
+        })
+    }
+}
+
+impl TryFrom<String> for TemperamentSetting {
+    type Error = ParseTemperamentError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<TemperamentSetting> for String {
+    fn from(setting: TemperamentSetting) -> Self {
+        setting.source
+    }
+}
+
+fn chromatic_names() -> Vec<String> {
+    NOTE_NAMES.iter().map(|n| n.to_string()).collect()
+}
+
+/// A pitch line of a Scala file: cents if it contains a period, a ratio otherwise
+fn parse_scl_pitch(line: &str) -> Result<f32, ParseSclError> {
+    let err = || ParseSclError(format!("invalid pitch: {:?}", line.trim()));
+    let value = line.split_whitespace().next().ok_or_else(err)?;
+    if value.contains('.') {
+        return value.parse().map_err(|_| err());
+    }
+    let (numerator, denominator) = value.split_once('/').unwrap_or((value, "1"));
+    let numerator: f32 = numerator.parse().map_err(|_| err())?;
+    let denominator: f32 = denominator.parse().map_err(|_| err())?;
+    if numerator <= 0.0 || denominator <= 0.0 {
+        return Err(err());
+    }
+    Ok(1200.0 * (numerator / denominator).log2())
+}
+
+/// Malformed Scala tuning file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSclError(pub String);
+
+impl fmt::Display for ParseSclError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid scala file: {}", self.0)
+    }
+}
+
+impl Error for ParseSclError {}
+
+/// Text that names no built-in temperament, or a Scala file that cannot be used as one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTemperamentError(pub String);
+
+impl fmt::Display for ParseTemperamentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid temperament: {}", self.0)
+    }
+}
+
+impl Error for ParseTemperamentError {}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn equal_temperament_needs_a_step() {
+        assert!(Temperament::equal(0).is_none());
+        assert_eq!(Temperament::equal(24).unwrap().degree_names[1], "C+");
+    }
+
+    #[test]
+    fn scala_pitches_need_not_increase() {
+        let temperament = Temperament::parse_scl("! descending\nDown\n3\n9/8\n1.0\n2/1\n").unwrap();
+        assert_eq!(temperament.degrees.len(), 3);
+        assert_eq!(temperament.degrees[2], 1.0);
+        assert_eq!(temperament.period, 1200.0);
+    }
+
+    #[test]
+    fn scala_files_load_relative_to_a_directory() {
+        let dir = env::temp_dir().join("transcriber-temperament-test");
+        fs::create_dir_all(&dir).unwrap();
+        let twelve: String = (1..=12).map(|i| format!("{}.0\n", i * 100)).collect();
+        fs::write(dir.join("twelve.scl"), format!("Twelve\n12\n{}", twelve)).unwrap();
+        fs::write(
+            dir.join("five.scl"),
+            "Five\n5\n240.\n480.\n720.\n960.\n2/1\n",
+        )
+        .unwrap();
+
+        let mut setting: TemperamentSetting = "twelve.scl".parse().unwrap();
+        assert_eq!(setting.offsets(), None);
+        setting.load(&dir).unwrap();
+        assert_eq!(setting.offsets(), Some([0.0; 12]));
+        assert_eq!(setting.to_string(), "twelve.scl");
+
+        let mut setting: TemperamentSetting = "five.scl".parse().unwrap();
+        let err = setting.load(&dir).unwrap_err();
+        assert!(err.0.contains("5 degrees"), "{}", err);
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    algorithms::{
+        peak_picking::peak_times,
+        shared::{XorShift, standardize},
+    },
+    annotations::{Annotation, AnnotationFormat},
+    batch::Batch,
+    config::OnsetConfig,
+    evaluation::{Scores, onset_scores},
+    samples::{Samples, file_to_samples},
+};
+
+/// Extensions of the annotation files looked up next to each recording, in order of
+/// preference
+const ANNOTATION_EXTENSIONS: [&str; 6] = ["mid", "midi", "txt", "csv", "lab", "onsets"];
+
+/// Values tried for every onset detection parameter
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchSpace {
+    pub frame_size: Vec<usize>,
+    pub hop_size: Vec<usize>,
+    pub window: Vec<usize>,
+    pub multiplier: Vec<usize>,
+    pub delta: Vec<f64>,
+    pub alpha: Vec<f64>,
+}
+
+impl Default for SearchSpace {
+    fn default() -> Self {
+        SearchSpace {
+            frame_size: vec![1024, 2048, 4096],
+            hop_size: vec![220, 441],
+            window: vec![2, 3, 4, 5],
+            multiplier: vec![1, 2, 3],
+            delta: vec![0.1, 0.2, 0.3, 0.5, 0.7, 1.0],
+            alpha: vec![0.2, 0.4, 0.6, 0.8, 0.9],
+        }
+    }
+}
+
+impl SearchSpace {
+    /// Number of combinations of the grid
+    pub fn len(&self) -> usize {
+        self.sizes().iter().product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn sizes(&self) -> [usize; 6] {
+        [
+            self.frame_size.len(),
+            self.hop_size.len(),
+            self.window.len(),
+            self.multiplier.len(),
+            self.delta.len(),
+            self.alpha.len(),
+        ]
+    }
+
+    /// Combination `index` of the grid, with the values of `base` for the other fields
+    fn candidate(&self, base: &OnsetConfig, index: usize) -> OnsetConfig {
+        let mut digits = [0; 6];
+        let mut rest = index;
+        for (digit, size) in digits.iter_mut().zip(self.sizes()).rev() {
+            *digit = rest % size;
+            rest /= size;
+        }
+        OnsetConfig {
+            frame_size: self.frame_size[digits[0]],
+            hop_size: self.hop_size[digits[1]],
+            window: self.window[digits[2]],
+            multiplier: self.multiplier[digits[3]],
+            delta: self.delta[digits[4]],
+            alpha: self.alpha[digits[5]],
+            ..base.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Search {
+    /// Every combination of the search space
+    Grid,
+    /// `trials` distinct combinations drawn at random, reproducible for a given `seed`
+    Random { trials: usize, seed: u64 },
+}
+
+/// A recording with its reference onsets in seconds
+#[derive(Clone)]
+pub struct Example {
+    pub samples: Samples,
+    pub onsets: Vec<f32>,
+}
+
+/// Searches the onset detection parameters that maximize the mean onset F-measure
+/// over a set of annotated recordings
+#[derive(Debug, Clone)]
+pub struct Tuner {
+    pub space: SearchSpace,
+    pub search: Search,
+    /// Maximum onset deviation in seconds
+    pub tolerance: f32,
+}
+
+/// Result of [`Tuner::tune`]
+#[derive(Debug, Clone)]
+pub struct TuningResult {
+    /// Best configuration found
+    pub config: OnsetConfig,
+    /// Scores of the best configuration, averaged over the examples
+    pub scores: Scores,
+    /// Number of configurations evaluated
+    pub trials: usize,
+}
+
+impl Default for Tuner {
+    fn default() -> Self {
+        Tuner {
+            space: SearchSpace::default(),
+            search: Search::Grid,
+            tolerance: 0.05,
+        }
+    }
+}
+
+impl Tuner {
+    pub fn new(search: Search) -> Self {
+        Tuner {
+            search,
+            ..Default::default()
+        }
+    }
+
+    /// Evaluates the candidates of the search space on `examples` and returns the best
+    /// one. Fields outside the space, such as the detection function kind, are taken
+    /// from `base`, which is always evaluated and wins ties.
+    pub fn tune(&self, base: &OnsetConfig, examples: &[Example]) -> TuningResult {
+        let mut candidates: Vec<OnsetConfig> = self
+            .candidate_indices()
+            .into_iter()
+            .map(|index| self.space.candidate(base, index))
+            .collect();
+        candidates.retain(|candidate| candidate != base);
+        candidates.insert(0, base.clone());
+
+        let mut scores = vec![Scores::default(); candidates.len()];
+        let mut odfs: Vec<Vec<f32>> = Vec::new();
+        let mut analysis = None;
+
+        // Grouped by frame and hop size, so that every ODF is computed once
+        let mut order: Vec<usize> = (0..candidates.len()).collect();
+        order.sort_by_key(|&i| (candidates[i].frame_size, candidates[i].hop_size));
+        for i in order {
+            let candidate = &candidates[i];
+            let key = (candidate.frame_size, candidate.hop_size);
+            if analysis != Some(key) {
+                odfs = examples
+                    .iter()
+                    .map(|example| {
+                        let mut odf = candidate.detection_function(&example.samples);
+                        standardize(&mut odf);
+                        odf
+                    })
+                    .collect();
+                analysis = Some(key);
+            }
+            scores[i] = self.evaluate(candidate, examples, &odfs);
+        }
+
+        let mut best = 0;
+        for (i, candidate) in scores.iter().enumerate() {
+            if candidate.f_measure > scores[best].f_measure {
+                best = i;
+            }
+        }
+        TuningResult {
+            config: candidates[best].clone(),
+            scores: scores[best],
+            trials: candidates.len(),
+        }
+    }
+
+    /// Mean scores of `config` over `examples`, given their standardized ODFs
+    fn evaluate(&self, config: &OnsetConfig, examples: &[Example], odfs: &[Vec<f32>]) -> Scores {
+        let mut total = Scores::default();
+        for (example, odf) in examples.iter().zip(odfs.iter()) {
+            let peaks = config.peaks(&mut odf.clone());
+            let times = peak_times(&peaks, config.timing(example.samples.spec.sample_rate));
+            let scores = onset_scores(&example.onsets, &times, self.tolerance);
+            total.precision += scores.precision;
+            total.recall += scores.recall;
+            total.f_measure += scores.f_measure;
+        }
+        let count = examples.len().max(1) as f32;
+        Scores {
+            precision: total.precision / count,
+            recall: total.recall / count,
+            f_measure: total.f_measure / count,
+        }
+    }
+
+    /// Grid indices to evaluate, in grid order
+    fn candidate_indices(&self) -> Vec<usize> {
+        let len = self.space.len();
+        match self.search {
+            Search::Grid => (0..len).collect(),
+            Search::Random { trials, .. } if trials >= len => (0..len).collect(),
+            Search::Random { trials, seed } => {
+                // Partial Fisher-Yates shuffle
+                let mut rng = XorShift::new(seed);
+                let mut indices: Vec<usize> = (0..len).collect();
+                for i in 0..trials {
+                    let j = i + rng.next_index(len - i);
+                    indices.swap(i, j);
+                }
+                indices.truncate(trials);
+                indices.sort_unstable();
+                indices
+            }
+        }
+    }
+}
+
+impl fmt::Display for TuningResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let config = &self.config;
+        write!(
+            f,
+            "frame {} hop {} window {} multiplier {} delta {} alpha {}: {} ({} trials)",
+            config.frame_size,
+            config.hop_size,
+            config.window,
+            config.multiplier,
+            config.delta,
+            config.alpha,
+            self.scores,
+            self.trials
+        )
+    }
+}
+
+/// Annotated recordings found by [`load_examples`]
+#[derive(Clone, Default)]
+pub struct Examples {
+    pub examples: Vec<Example>,
+    /// Recordings or annotations that could not be read, with the error
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+/// Loads every WAV file under `dir` that has an annotation with the same name and one
+/// of the extensions .mid, .midi, .txt, .csv, .lab or .onsets. Recordings without an
+/// annotation are ignored, unreadable ones are skipped and reported.
+pub fn load_examples(
+    dir: &Path,
+    format: Option<AnnotationFormat>,
+) -> Result<Examples, Box<dyn Error>> {
+    let mut loaded = Examples::default();
+    for input in Batch::default().inputs(dir)? {
+        let Some(reference) = annotation_path(&input) else {
+            continue;
+        };
+        let annotation = match Annotation::from_file(&reference, format) {
+            Ok(annotation) => annotation,
+            Err(error) => {
+                loaded.skipped.push((reference, error.to_string()));
+                continue;
+            }
+        };
+        match file_to_samples(&input) {
+            Ok(samples) => loaded.examples.push(Example {
+                samples,
+                onsets: annotation.onsets(),
+            }),
+            Err(error) => loaded.skipped.push((input, error.to_string())),
+        }
+    }
+    Ok(loaded)
+}
+
+fn annotation_path(input: &Path) -> Option<PathBuf> {
+    ANNOTATION_EXTENSIONS
+        .iter()
+        .map(|extension| input.with_extension(extension))
+        .find(|path| path.is_file())
+}
@@ -0,0 +1,187 @@
+use crate::{
+    algorithms::pitch::PitchTrack,
+    notes::{A4_INDEX, B8_INDEX, NOTE_NAMES, Note},
+    scales::ScaleConstraint,
+    temperament::Temperament,
+};
+
+/// Concert pitch and temperament used to map frequencies to notes, optionally
+/// restricted to the notes of a scale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tuning {
+    /// Frequency of A4 in Hz
+    pub a4: f32,
+    pub scale: Option<ScaleConstraint>,
+    /// Deviation in cents of every pitch class from 12-TET, starting at C, with A at 0.
+    /// All zero in 12-TET.
+    pub offsets: [f32; 12],
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Tuning::new(440.0)
+    }
+}
+
+impl Tuning {
+    pub fn new(a4: f32) -> Self {
+        Tuning {
+            a4,
+            scale: None,
+            offsets: [0.0; 12],
+        }
+    }
+
+    /// Tuning shifted `cents` away from A4 = 440 Hz
+    pub fn from_cents(cents: f32) -> Self {
+        Tuning::new(440.0 * 2.0f32.powf(cents / 1200.0))
+    }
+
+    pub fn with_scale(self, scale: ScaleConstraint) -> Self {
+        Tuning {
+            scale: Some(scale),
+            ..self
+        }
+    }
+
+    /// Tuning with the pitch classes of `temperament`, or `None` if it does not have
+    /// 12 degrees to the octave
+    pub fn with_temperament(self, temperament: &Temperament) -> Option<Self> {
+        Some(Tuning {
+            offsets: temperament.pitch_class_offsets()?,
+            ..self
+        })
+    }
+
+    /// Deviation of this reference from A4 = 440 Hz, in cents
+    pub fn cents(&self) -> f32 {
+        1200.0 * (self.a4 / 440.0).log2()
+    }
+
+    /// Fractional number of semitones between C0 and `freq`, interpolated linearly
+    /// between the tempered notes. Infinite for 0 Hz and infinite frequencies.
+    pub fn semitones(&self, freq: f32) -> f32 {
+        let equal = 12.0 * (freq / self.a4).log2() + A4_INDEX as f32;
+        // 0 Hz and infinity have no neighbouring notes to interpolate between
+        if !equal.is_finite() {
+            return equal;
+        }
+        let position = |note: i32| note as f32 + self.offset(note) / 100.0;
+
+        let mut note = equal.floor() as i32;
+        if equal < position(note) {
+            note -= 1;
+        } else if equal >= position(note + 1) {
+            note += 1;
+        }
+        note as f32 + (equal - position(note)) / (position(note + 1) - position(note))
+    }
+
+    /// Frequency of the note `semitones` above C0
+    pub fn freq(&self, semitones: i32) -> f32 {
+        let cents = (semitones - A4_INDEX) as f32 * 100.0 + self.offset(semitones);
+        self.a4 * 2.0f32.powf(cents / 1200.0)
+    }
+
+    fn offset(&self, semitones: i32) -> f32 {
+        self.offsets[semitones.rem_euclid(12) as usize]
+    }
+
+    /// Nearest note to `freq` between C0 and B8, subject to the scale constraint unless
+    /// its scale is empty
+    pub fn note(&self, freq: f32) -> Note {
+        let index = self.semitones(freq).round().clamp(0.0, B8_INDEX as f32) as i32;
+        match self.scale.filter(|constraint| !constraint.scale.is_empty()) {
+            Some(scale) => {
+                // Wide enough to reach a note of any non-empty scale, even at the edges
+                let candidates = (index - 11).max(0)..=(index + 11).min(B8_INDEX);
+                scale
+                    .choose(freq, candidates.map(|i| self.chromatic_note(i)))
+                    .unwrap()
+            }
+            None => self.chromatic_note(index),
+        }
+    }
+
+    fn chromatic_note(&self, semitones: i32) -> Note {
+        Note::new(
+            self.freq(semitones),
+            semitones as usize / 12,
+            NOTE_NAMES[semitones as usize % 12],
+        )
+    }
+
+    /// Nearest note to `freq` along with the signed deviation from it in cents
+    pub fn nearest(&self, freq: f32) -> (Note, f32) {
+        let note = self.note(freq);
+        (note, note.cents(freq))
+    }
+
+    /// Estimates the reference of a recording from the cents histogram of its voiced
+    /// frames, relative to A4 = 440 Hz. Falls back to the default tuning if there
+    /// are no frames above `min_voiced_prob`.
+    ///
+    /// The offset is folded into ±50 cents, so a reference a whole semitone away
+    /// (e.g. A4 = 415 Hz) is indistinguishable from A4 = 440 Hz.
+    pub fn estimate(track: &PitchTrack, min_voiced_prob: f32) -> Self {
+        Tuning::default().estimated(track, min_voiced_prob)
+    }
+
+    /// As [`Tuning::estimate`], measuring the deviations from the notes of this
+    /// temperament and keeping its scale constraint
+    pub fn estimated(self, track: &PitchTrack, min_voiced_prob: f32) -> Self {
+        const BINS: usize = 100;
+        const SMOOTHING: i32 = 5;
+
+        let reference = Tuning { a4: 440.0, ..self };
+        let deviations: Vec<f32> = track
+            .f0(min_voiced_prob)
+            .into_iter()
+            .flatten()
+            .filter(|f| *f > 0.0)
+            .map(|f| {
+                let semitones = reference.semitones(f);
+                (semitones - semitones.round()) * 100.0
+            })
+            .collect();
+
+        if deviations.is_empty() {
+            return reference;
+        }
+
+        // Circular histogram of deviations in [-50, 50) cents
+        let mut histogram = [0.0f32; BINS];
+        for d in deviations.iter() {
+            let bin = (d.round() as i32 + 50).rem_euclid(BINS as i32) as usize;
+            histogram[bin] += 1.0;
+        }
+
+        let smoothed: Vec<f32> = (0..BINS as i32)
+            .map(|i| {
+                (-SMOOTHING..=SMOOTHING)
+                    .map(|j| histogram[(i + j).rem_euclid(BINS as i32) as usize])
+                    .sum()
+            })
+            .collect();
+        let peak = smoothed
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, _)| i as f32 - 50.0)
+            .unwrap();
+
+        // Refine the peak with the mean of the deviations around it
+        let wrap = |d: f32| (d + 50.0).rem_euclid(100.0) - 50.0;
+        let near: Vec<f32> = deviations
+            .iter()
+            .map(|d| wrap(d - peak))
+            .filter(|d| d.abs() <= 2.0 * SMOOTHING as f32)
+            .collect();
+        let refinement = near.iter().sum::<f32>() / near.len().max(1) as f32;
+
+        Tuning {
+            a4: Tuning::from_cents(wrap(peak + refinement)).a4,
+            ..self
+        }
+    }
+}